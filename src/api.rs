@@ -1,11 +1,28 @@
+use crate::jwt;
 use reqwest::blocking::ClientBuilder;
 // use reqwest::blocking::Client;
 use reqwest::header;
 use serde::{Deserialize, Serialize};
-use serde_json;
 use std::collections::HashMap;
 use std::time::Duration;
 
+/// Default total request timeout; FSQL queries can run for a long time
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(650);
+
+/// Where and how commands are sent to the FSQL API
+#[derive(Clone)]
+pub struct Connection {
+    /// Full URL of the FSQL endpoint
+    pub url: String,
+    /// Bearer token or API key
+    pub token: String,
+    /// Total time allowed for a request
+    pub timeout: Duration,
+    /// Warn before dispatching when the token expires within this long
+    pub expiry_warning: Duration,
+    pub verbose: bool,
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 struct PostData {
     q: String,
@@ -71,7 +88,7 @@ pub struct ValidateResponse {
 }
 
 /// JSON Response details for the FSQL QUERY command
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct QueryResponse {
     pub command: String,
     pub search_id: String,
@@ -79,13 +96,209 @@ pub struct QueryResponse {
     pub results: Vec<serde_json::Value>,
 }
 
+/// A response from the FSQL API, selected by the `command` field of the body
+#[derive(Serialize)]
+#[serde(untagged)]
+pub enum FsqlResponse {
+    ExplainConnectors(ExplainConnectorsResponse),
+    ExplainVersion(ExplainVersionResponse),
+    ExplainSchema(ExplainSchemaResponse),
+    ExplainGraphql(ExplainGraphqlResponse),
+    ExplainAttributes(ExplainAttributesResponse),
+    Explain(ExplainResponse),
+    Summarize(SummarizeResponse),
+    Validate(ValidateResponse),
+    Query(QueryResponse),
+}
+
+impl<'de> Deserialize<'de> for FsqlResponse {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        use serde::de::Error;
+
+        let value = serde_json::Value::deserialize(deserializer)?;
+        let command = value
+            .get("command")
+            .and_then(serde_json::Value::as_str)
+            .ok_or_else(|| D::Error::missing_field("command"))?;
+
+        // The API isn't consistent about how it spells commands, so compare
+        // without case, whitespace or punctuation (EXPLAIN_SCHEMA == explain schema)
+        let normalized: String = command
+            .chars()
+            .filter(|c| c.is_alphanumeric())
+            .collect::<String>()
+            .to_uppercase();
+
+        let response = if let Some(rest) = normalized.strip_prefix("EXPLAIN") {
+            if rest.starts_with("CONNECTORS") {
+                serde_json::from_value(value).map(Self::ExplainConnectors)
+            } else if rest.starts_with("VERSION") {
+                serde_json::from_value(value).map(Self::ExplainVersion)
+            } else if rest.starts_with("SCHEMA") {
+                serde_json::from_value(value).map(Self::ExplainSchema)
+            } else if rest.starts_with("GRAPHQL") {
+                serde_json::from_value(value).map(Self::ExplainGraphql)
+            } else if rest.starts_with("ATTRIBUTES") {
+                serde_json::from_value(value).map(Self::ExplainAttributes)
+            } else {
+                serde_json::from_value(value).map(Self::Explain)
+            }
+        } else if normalized.starts_with("SUMMARIZE") {
+            serde_json::from_value(value).map(Self::Summarize)
+        } else if normalized.starts_with("VALIDATE") {
+            serde_json::from_value(value).map(Self::Validate)
+        } else if normalized.starts_with("QUERY") {
+            serde_json::from_value(value).map(Self::Query)
+        } else {
+            return Err(D::Error::custom(format!("unknown command '{}'", command)));
+        };
+
+        response.map_err(D::Error::custom)
+    }
+}
+
+/// Details of an error response returned by the FSQL API
+#[derive(Debug)]
+pub struct ServerError {
+    /// HTTP status code of the response
+    pub status: reqwest::StatusCode,
+    /// Human readable message extracted from the body (or the body itself)
+    pub message: String,
+    /// Machine readable error code, if the server provided one
+    pub code: Option<String>,
+}
+
+impl ServerError {
+    /// Build from a status code and response body
+    ///
+    /// The body is parsed as JSON where possible, looking for the fields that
+    /// error responses commonly use. Anything else is used verbatim.
+    fn from_body(status: reqwest::StatusCode, body: &str) -> Self {
+        let parsed = serde_json::from_str::<serde_json::Value>(body).ok();
+        let field = |name: &str| {
+            parsed
+                .as_ref()
+                .and_then(|value| value.get(name))
+                .and_then(|value| match value {
+                    serde_json::Value::String(s) => Some(s.clone()),
+                    serde_json::Value::Number(n) => Some(n.to_string()),
+                    _ => None,
+                })
+        };
+
+        let message = field("message")
+            .or_else(|| field("error"))
+            .or_else(|| field("detail"))
+            .unwrap_or_else(|| body.trim().to_string());
+        let code = field("code").or_else(|| field("error_code"));
+
+        Self {
+            status,
+            message,
+            code,
+        }
+    }
+}
+
+impl std::fmt::Display for ServerError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "HTTP {}", self.status.as_u16())?;
+        if let Some(code) = &self.code {
+            write!(f, " [{}]", code)?;
+        }
+        if self.message.is_empty() {
+            Ok(())
+        } else {
+            write!(f, ": {}", self.message)
+        }
+    }
+}
+
+/// Errors that can occur while dispatching a command to the FSQL API
+#[derive(Debug)]
+pub enum ApiError {
+    /// The request could not be built, sent, or its response read
+    Transport(reqwest::Error),
+    /// The request didn't complete within the configured timeout
+    Timeout(reqwest::Error),
+    /// Ctrl+C was pressed before the response arrived
+    Cancelled,
+    /// HTTP 401 - the token is missing, invalid or expired
+    Unauthorized(ServerError),
+    /// HTTP 403 - the token is valid but lacks the required permissions
+    Forbidden(ServerError),
+    /// Any other 4xx response
+    Client(ServerError),
+    /// A 5xx (or otherwise unexpected) response
+    Server(ServerError),
+    /// The response body wasn't a response we understand
+    Decode {
+        source: serde_json::Error,
+        body: String,
+    },
+}
+
+impl ApiError {
+    /// Classify an unsuccessful response by its status code
+    fn from_status(status: reqwest::StatusCode, body: &str) -> Self {
+        let error = ServerError::from_body(status, body);
+        match status.as_u16() {
+            401 => Self::Unauthorized(error),
+            403 => Self::Forbidden(error),
+            400..=499 => Self::Client(error),
+            _ => Self::Server(error),
+        }
+    }
+
+    /// Classify an error raised by the HTTP client
+    fn from_reqwest(error: reqwest::Error) -> Self {
+        if error.is_timeout() {
+            Self::Timeout(error)
+        } else {
+            Self::Transport(error)
+        }
+    }
+}
+
+impl std::fmt::Display for ApiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Transport(e) => write!(f, "Request failed: {}", e),
+            Self::Timeout(e) => write!(f, "Request timed out: {}", e),
+            Self::Cancelled => write!(f, "Request cancelled"),
+            Self::Unauthorized(e) => write!(
+                f,
+                "Unauthorized ({}) - check that your token is correct and has not expired",
+                e
+            ),
+            Self::Forbidden(e) => write!(
+                f,
+                "Forbidden ({}) - your token lacks permission for this request",
+                e
+            ),
+            Self::Client(e) => write!(f, "Request rejected by server ({})", e),
+            Self::Server(e) => write!(f, "Server error ({})", e),
+            Self::Decode { source, .. } => write!(f, "Failed to decode response: {}", source),
+        }
+    }
+}
+
+impl std::error::Error for ApiError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Transport(e) | Self::Timeout(e) => Some(e),
+            Self::Decode { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
 /// Remove the Bearer prefix (if present) from a given token string
 fn strip_bearer_prefix(token: &str) -> &str {
-    if token.starts_with("Bearer ") {
-        &token[7..] // Remove "Bearer " (7 characters)
-    } else {
-        token
-    }
+    token.strip_prefix("Bearer ").unwrap_or(token)
 }
 
 /// Attempt to whether the given string is a JWT or API token
@@ -153,13 +366,19 @@ fn add_headers(
     request_builder.headers(request_headers)
 }
 
-/// Dispatch an FSQL command and print the response
-pub fn dispatch_command(
-    query: &str,
-    api: &str,
-    token: &str,
-    verbose: bool,
-) -> Result<String, Box<dyn std::error::Error>> {
+/// Dispatch an FSQL command and parse the response
+pub fn dispatch_command(query: &str, connection: &Connection) -> Result<FsqlResponse, ApiError> {
+    let Connection {
+        url: api,
+        token,
+        timeout,
+        expiry_warning,
+        verbose,
+    } = connection;
+    let verbose = *verbose;
+
+    jwt::warn_before_dispatch(token, *expiry_warning);
+
     let data = PostData {
         q: query.to_string(),
     };
@@ -174,8 +393,9 @@ pub fn dispatch_command(
 
     let client = ClientBuilder::new()
         .connect_timeout(Duration::from_secs(10)) // Time to establish connection
-        .timeout(Duration::from_secs(650)) // Total request timeout
-        .build()?;
+        .timeout(*timeout) // Total request timeout
+        .build()
+        .map_err(ApiError::Transport)?;
 
     // Build request with appropriate auth header based on token type
     let request = add_headers(client.post(api), token, verbose).json(&data);
@@ -200,13 +420,6 @@ pub fn dispatch_command(
                 for (key, value) in response.headers() {
                     eprintln!("   {}: {}", key, value.to_str().unwrap_or("<non-utf8>"));
                 }
-
-                // Special handling for 401/403 responses
-                if response.status() == 401 {
-                    eprintln!("🚨 HTTP 401 Unauthorized - Check your token!");
-                } else if response.status() == 403 {
-                    eprintln!("🚨 HTTP 403 Forbidden - Token valid but insufficient permissions!");
-                }
             }
 
             response
@@ -214,7 +427,6 @@ pub fn dispatch_command(
         Err(e) => {
             if verbose {
                 eprintln!("❌ Request failed to send!");
-                eprintln!("🔍 Error type: {}", std::any::type_name_of_val(&e));
 
                 // Provide more specific error information
                 if e.is_timeout() {
@@ -232,7 +444,7 @@ pub fn dispatch_command(
                 }
             }
 
-            return Err(Box::new(e));
+            return Err(ApiError::from_reqwest(e));
         }
     };
 
@@ -255,34 +467,20 @@ pub fn dispatch_command(
         }
 
         // Try to read error response body
-        match response.text() {
-            Ok(error_body) => {
-                if verbose {
-                    eprintln!("📄 Error response body:");
-                    eprintln!("{}", error_body);
-
-                    // Look for common auth error patterns
-                    if error_body.to_lowercase().contains("unauthorized") {
-                        eprintln!("💡 Server says 'unauthorized' - likely a token issue");
-                    } else if error_body.to_lowercase().contains("invalid")
-                        && error_body.to_lowercase().contains("token")
-                    {
-                        eprintln!("💡 Server says invalid token - check token format/expiration");
-                    }
-                }
-                return Err(format!("Server returned error {}: {}", status, error_body).into());
-            }
+        let error_body = match response.text() {
+            Ok(error_body) => error_body,
             Err(body_err) => {
                 if verbose {
                     eprintln!("❌ Could not read error response body: {}", body_err);
                 }
-                return Err(format!(
-                    "Server returned error {} (could not read response body)",
-                    status
-                )
-                .into());
+                String::new()
             }
+        };
+        if verbose {
+            eprintln!("📄 Error response body:");
+            eprintln!("{}", error_body);
         }
+        return Err(ApiError::from_status(status, &error_body));
     }
 
     // Try to read the response body
@@ -290,6 +488,63 @@ pub fn dispatch_command(
         eprintln!("📖 Reading response body...");
     }
 
-    let response_text = response.text().unwrap();
-    Ok(response_text)
+    let response_text = response.text().map_err(ApiError::from_reqwest)?;
+    serde_json::from_str::<FsqlResponse>(&response_text).map_err(|source| {
+        if verbose {
+            eprintln!("❌ Failed to parse response as JSON: {}", source);
+        }
+        ApiError::Decode {
+            source,
+            body: response_text,
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_response_selected_by_command() {
+        let body = r#"{"command": "QUERY", "search_id": "s", "trace_id": "t", "results": [{}]}"#;
+        let response: FsqlResponse = serde_json::from_str(body).unwrap();
+        assert!(matches!(response, FsqlResponse::Query(ref data) if data.results.len() == 1));
+
+        let body = r#"{"command": "explain_schema", "schema": {}}"#;
+        let response: FsqlResponse = serde_json::from_str(body).unwrap();
+        assert!(matches!(response, FsqlResponse::ExplainSchema(_)));
+
+        let body = r#"{"command": "EXPLAIN", "input": "x", "expanded_query": "y"}"#;
+        let response: FsqlResponse = serde_json::from_str(body).unwrap();
+        assert!(matches!(response, FsqlResponse::Explain(_)));
+    }
+
+    #[test]
+    fn test_response_decode_errors() {
+        assert!(serde_json::from_str::<FsqlResponse>(r#"{"is_valid": true}"#).is_err());
+        assert!(serde_json::from_str::<FsqlResponse>(r#"{"command": "DROP"}"#).is_err());
+        assert!(serde_json::from_str::<FsqlResponse>(r#"{"command": "VALIDATE"}"#).is_err());
+    }
+
+    #[test]
+    fn test_error_classification() {
+        let body = r#"{"message": "token expired", "code": "AUTH_001"}"#;
+        let error = ApiError::from_status(reqwest::StatusCode::UNAUTHORIZED, body);
+        match error {
+            ApiError::Unauthorized(e) => {
+                assert_eq!(e.message, "token expired");
+                assert_eq!(e.code.as_deref(), Some("AUTH_001"));
+            }
+            other => panic!("unexpected error: {other}"),
+        }
+
+        let error = ApiError::from_status(reqwest::StatusCode::FORBIDDEN, "");
+        assert!(matches!(error, ApiError::Forbidden(_)));
+
+        let error = ApiError::from_status(reqwest::StatusCode::BAD_REQUEST, "bad query");
+        assert!(matches!(error, ApiError::Client(ref e) if e.message == "bad query"));
+
+        let error = ApiError::from_status(reqwest::StatusCode::BAD_GATEWAY, "<html>");
+        assert!(matches!(error, ApiError::Server(_)));
+    }
 }
@@ -0,0 +1,248 @@
+//! Columnar (Parquet and Arrow IPC) export of QUERY results
+//!
+//! Results don't come with a schema, so one is inferred by walking every
+//! event. Nested objects become structs and arrays become lists. Where the
+//! same path holds values of different types the column falls back to a
+//! string, so heterogeneous results never fail to export.
+
+use arrow_array::RecordBatch;
+use arrow_schema::{DataType, Field, Fields, Schema};
+use serde_json::{Map, Value};
+use std::sync::Arc;
+
+/// The type inferred for a JSON path
+#[derive(Debug, Clone, PartialEq)]
+enum Kind {
+    /// Only nulls have been seen so far
+    Null,
+    Bool,
+    Int,
+    Float,
+    Utf8,
+    List(Box<Kind>),
+    Struct(Vec<(String, Kind)>),
+}
+
+impl Kind {
+    /// Infer the kind of a single value
+    fn of(value: &Value) -> Self {
+        match value {
+            Value::Null => Self::Null,
+            Value::Bool(_) => Self::Bool,
+            Value::Number(n) if n.is_i64() => Self::Int,
+            Value::Number(_) => Self::Float,
+            Value::String(_) => Self::Utf8,
+            Value::Array(items) => Self::List(Box::new(
+                items.iter().map(Self::of).fold(Self::Null, Self::merge),
+            )),
+            Value::Object(map) => Self::Struct(
+                map.iter()
+                    .map(|(key, value)| (key.clone(), Self::of(value)))
+                    .collect(),
+            ),
+        }
+    }
+
+    /// Combine the kinds of two values seen at the same path
+    fn merge(self, other: Self) -> Self {
+        match (self, other) {
+            (Self::Null, kind) | (kind, Self::Null) => kind,
+            (Self::Int, Self::Float) | (Self::Float, Self::Int) => Self::Float,
+            (Self::List(a), Self::List(b)) => Self::List(Box::new(a.merge(*b))),
+            (Self::Struct(mut fields), Self::Struct(others)) => {
+                for (key, kind) in others {
+                    match fields.iter_mut().find(|(name, _)| *name == key) {
+                        Some((_, existing)) => {
+                            *existing = std::mem::replace(existing, Self::Null).merge(kind)
+                        }
+                        None => fields.push((key, kind)),
+                    }
+                }
+                Self::Struct(fields)
+            }
+            (a, b) if a == b => a,
+            // Conflicting types are kept as text
+            _ => Self::Utf8,
+        }
+    }
+
+    /// The Arrow type used to store values of this kind
+    fn data_type(&self) -> DataType {
+        match self {
+            Self::Null | Self::Utf8 => DataType::Utf8,
+            Self::Bool => DataType::Boolean,
+            Self::Int => DataType::Int64,
+            Self::Float => DataType::Float64,
+            Self::List(item) => {
+                DataType::List(Arc::new(Field::new("item", item.data_type(), true)))
+            }
+            // Parquet can't store structs without fields, so keep these as text
+            Self::Struct(fields) if fields.is_empty() => DataType::Utf8,
+            Self::Struct(fields) => DataType::Struct(struct_fields(fields)),
+        }
+    }
+
+    /// Convert a value so that it matches this kind
+    ///
+    /// Values at a path which fell back to text are rendered as JSON.
+    fn coerce(&self, value: &Value) -> Value {
+        match (self, value) {
+            (_, Value::Null) => Value::Null,
+            (Self::Utf8 | Self::Null, Value::String(_)) => value.clone(),
+            (Self::Utf8 | Self::Null, _) => Value::String(value.to_string()),
+            (Self::Struct(fields), _) if fields.is_empty() => Value::String(value.to_string()),
+            (Self::List(item), Value::Array(items)) => {
+                Value::Array(items.iter().map(|value| item.coerce(value)).collect())
+            }
+            (Self::Struct(fields), Value::Object(map)) => Value::Object(coerce_fields(fields, map)),
+            _ => value.clone(),
+        }
+    }
+}
+
+fn struct_fields(fields: &[(String, Kind)]) -> Fields {
+    fields
+        .iter()
+        .map(|(name, kind)| Field::new(name, kind.data_type(), true))
+        .collect()
+}
+
+fn coerce_fields(fields: &[(String, Kind)], map: &Map<String, Value>) -> Map<String, Value> {
+    fields
+        .iter()
+        .filter_map(|(name, kind)| {
+            map.get(name)
+                .map(|value| (name.clone(), kind.coerce(value)))
+        })
+        .collect()
+}
+
+/// Convert query results into a single Arrow record batch
+///
+/// Each top-level key of the events becomes a column. If any result isn't a
+/// JSON object, every result is placed in a single `value` column instead.
+pub fn to_record_batch(results: &[Value]) -> Result<RecordBatch, Box<dyn std::error::Error>> {
+    let rows: Vec<Map<String, Value>> = if results.iter().all(Value::is_object) {
+        results
+            .iter()
+            .filter_map(|value| value.as_object().cloned())
+            .collect()
+    } else {
+        results
+            .iter()
+            .map(|value| Map::from_iter([("value".to_string(), value.clone())]))
+            .collect()
+    };
+
+    let fields = match rows
+        .iter()
+        .map(|row| Kind::of(&Value::Object(row.clone())))
+        .fold(Kind::Struct(Vec::new()), Kind::merge)
+    {
+        Kind::Struct(fields) => fields,
+        _ => unreachable!("rows are always objects"),
+    };
+    let schema = Arc::new(Schema::new(struct_fields(&fields)));
+
+    if rows.is_empty() || fields.is_empty() {
+        return Ok(RecordBatch::new_empty(schema));
+    }
+
+    let coerced: Vec<Map<String, Value>> =
+        rows.iter().map(|row| coerce_fields(&fields, row)).collect();
+    let mut decoder = arrow_json::ReaderBuilder::new(schema.clone()).build_decoder()?;
+    decoder.serialize(&coerced)?;
+    Ok(decoder
+        .flush()?
+        .unwrap_or_else(|| RecordBatch::new_empty(schema)))
+}
+
+/// Encode query results as a Parquet file
+pub fn to_parquet(results: &[Value]) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let batch = to_record_batch(results)?;
+    let mut buffer = Vec::new();
+    let mut writer = parquet::arrow::ArrowWriter::try_new(&mut buffer, batch.schema(), None)?;
+    writer.write(&batch)?;
+    writer.close()?;
+    Ok(buffer)
+}
+
+/// Encode query results as an Arrow IPC file
+pub fn to_arrow_ipc(results: &[Value]) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let batch = to_record_batch(results)?;
+    let mut buffer = Vec::new();
+    let mut writer = arrow_ipc::writer::FileWriter::try_new(&mut buffer, &batch.schema())?;
+    writer.write(&batch)?;
+    writer.finish()?;
+    drop(writer);
+    Ok(buffer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow_array::Array;
+    use serde_json::json;
+
+    #[test]
+    fn test_schema_inference_with_mixed_types() {
+        let results = [
+            json!({"pid": 1, "score": 1, "name": "a", "actor": {"uid": 5}, "tags": ["x"]}),
+            json!({"pid": "two", "score": 2.5, "actor": {"name": "bob"}, "empty": {}}),
+        ];
+        let batch = to_record_batch(&results).unwrap();
+        let schema = batch.schema();
+
+        assert_eq!(batch.num_rows(), 2);
+        assert_eq!(
+            schema.field_with_name("pid").unwrap().data_type(),
+            &DataType::Utf8
+        );
+        assert_eq!(
+            schema.field_with_name("score").unwrap().data_type(),
+            &DataType::Float64
+        );
+        assert_eq!(
+            schema.field_with_name("empty").unwrap().data_type(),
+            &DataType::Utf8
+        );
+        match schema.field_with_name("actor").unwrap().data_type() {
+            DataType::Struct(fields) => {
+                let names: Vec<&str> = fields.iter().map(|f| f.name().as_str()).collect();
+                assert_eq!(names, vec!["uid", "name"]);
+            }
+            other => panic!("expected struct, got {other}"),
+        }
+        assert!(matches!(
+            schema.field_with_name("tags").unwrap().data_type(),
+            DataType::List(_)
+        ));
+
+        let pid = batch.column_by_name("pid").unwrap();
+        let pid = pid
+            .as_any()
+            .downcast_ref::<arrow_array::StringArray>()
+            .unwrap();
+        assert_eq!(pid.value(0), "1");
+        assert_eq!(pid.value(1), "two");
+        assert!(batch.column_by_name("name").unwrap().is_null(1));
+    }
+
+    #[test]
+    fn test_non_object_results() {
+        let batch = to_record_batch(&[json!(1), json!({"a": 1})]).unwrap();
+        assert_eq!(batch.num_columns(), 1);
+        assert_eq!(batch.schema().field(0).name(), "value");
+    }
+
+    #[test]
+    fn test_encoders_produce_files() {
+        let results = [json!({"a": 1, "b": {"c": "d"}})];
+        let parquet = to_parquet(&results).unwrap();
+        assert_eq!(&parquet[..4], b"PAR1");
+        let arrow = to_arrow_ipc(&results).unwrap();
+        assert_eq!(&arrow[..6], b"ARROW1");
+
+        assert!(to_parquet(&[]).is_ok());
+    }
+}
@@ -0,0 +1,201 @@
+//! Parsing and dispatch of FSQL commands
+//!
+//! Both the REPL and the pipe/file/command modes route input through
+//! [`FsqlCommand::parse`] and [`execute`]. The only thing that differs between
+//! the modes is how a response is shown to the user, which is handled by an
+//! implementation of the [`Presenter`] trait.
+
+use crate::api::{self, ApiError, Connection, FsqlResponse};
+use crate::interrupt;
+
+/// The FSQL commands understood by fsqlctl
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FsqlCommand {
+    ExplainConnectors,
+    ExplainVersion,
+    ExplainSchema,
+    ExplainGraphql,
+    ExplainAttributes,
+    Explain,
+    Summarize,
+    Validate,
+    Query,
+}
+
+impl FsqlCommand {
+    /// Determine which command the given input represents
+    ///
+    /// Matching is case-insensitive and tolerant of newlines between words.
+    /// Returns `None` if the input isn't a recognised FSQL command, or if it
+    /// is missing the FSQL body that the command requires.
+    pub fn parse(input: &str) -> Option<Self> {
+        let mut words = input
+            .split_whitespace()
+            .map(|word| word.trim_end_matches(';').to_uppercase());
+        let verb = words.next()?;
+        let second = words.next();
+        let has_body = |word: &Option<String>| word.as_deref().is_some_and(|w| !w.is_empty());
+
+        match verb.as_str() {
+            "QUERY" if has_body(&second) => Some(Self::Query),
+            "VALIDATE" if has_body(&second) => Some(Self::Validate),
+            "SUMMARIZE" if has_body(&second) => Some(Self::Summarize),
+            "EXPLAIN" => {
+                let third = words.next();
+                match second.as_deref() {
+                    Some("CONNECTORS") => Some(Self::ExplainConnectors),
+                    Some("VERSION") => Some(Self::ExplainVersion),
+                    Some("SCHEMA") if has_body(&third) => Some(Self::ExplainSchema),
+                    Some("GRAPHQL") if has_body(&third) => Some(Self::ExplainGraphql),
+                    Some("ATTRIBUTES") if has_body(&third) => Some(Self::ExplainAttributes),
+                    Some("SCHEMA" | "GRAPHQL" | "ATTRIBUTES") => None,
+                    _ if has_body(&second) => Some(Self::Explain),
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Renders API responses for a particular output mode
+///
+/// The REPL writes human friendly output to stdout, whereas the pipe mode
+/// keeps stdout for the machine readable payload and sends everything else to
+/// stderr.
+pub trait Presenter {
+    fn explain_connectors(&mut self, data: &api::ExplainConnectorsResponse);
+    fn explain_version(&mut self, data: &api::ExplainVersionResponse);
+    fn explain_schema(&mut self, data: &api::ExplainSchemaResponse);
+    fn explain_graphql(&mut self, data: &api::ExplainGraphqlResponse);
+    fn explain_attributes(&mut self, data: &api::ExplainAttributesResponse);
+    fn explain(&mut self, data: &api::ExplainResponse);
+    fn summarize(&mut self, data: &api::SummarizeResponse);
+    fn validate(&mut self, data: &api::ValidateResponse);
+    fn query(&mut self, data: &api::QueryResponse);
+
+    /// Called with the response body when it couldn't be parsed
+    fn raw(&mut self, response_text: &str);
+}
+
+/// Dispatch a command to the FSQL API and hand the response to a presenter
+///
+/// Errors are only returned when the request itself fails; a response that
+/// can't be decoded is passed to [`Presenter::raw`].
+pub fn execute(
+    input: &str,
+    connection: &Connection,
+    presenter: &mut dyn Presenter,
+) -> Result<(), ApiError> {
+    handle_response(api::dispatch_command(input, connection), presenter)
+}
+
+/// Dispatch a command like [`execute`], but stop waiting for the response
+/// when Ctrl+C is pressed
+///
+/// Returns [`ApiError::Cancelled`] if the wait was interrupted.
+pub fn execute_interruptible(
+    input: &str,
+    connection: &Connection,
+    presenter: &mut dyn Presenter,
+) -> Result<(), ApiError> {
+    let (input, connection) = (input.to_string(), connection.clone());
+    let response = interrupt::run(move || api::dispatch_command(&input, &connection))
+        .unwrap_or(Err(ApiError::Cancelled));
+    handle_response(response, presenter)
+}
+
+/// Present a response, or the body of one that couldn't be decoded
+fn handle_response(
+    response: Result<FsqlResponse, ApiError>,
+    presenter: &mut dyn Presenter,
+) -> Result<(), ApiError> {
+    match response {
+        Ok(response) => present(&response, presenter),
+        Err(ApiError::Decode { body, .. }) => presenter.raw(&body),
+        Err(e) => return Err(e),
+    }
+    Ok(())
+}
+
+/// Pass a response to the matching presenter method
+pub fn present(response: &FsqlResponse, presenter: &mut dyn Presenter) {
+    match response {
+        FsqlResponse::ExplainConnectors(data) => presenter.explain_connectors(data),
+        FsqlResponse::ExplainVersion(data) => presenter.explain_version(data),
+        FsqlResponse::ExplainSchema(data) => presenter.explain_schema(data),
+        FsqlResponse::ExplainGraphql(data) => presenter.explain_graphql(data),
+        FsqlResponse::ExplainAttributes(data) => presenter.explain_attributes(data),
+        FsqlResponse::Explain(data) => presenter.explain(data),
+        FsqlResponse::Summarize(data) => presenter.summarize(data),
+        FsqlResponse::Validate(data) => presenter.validate(data),
+        FsqlResponse::Query(data) => presenter.query(data),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_commands() {
+        assert_eq!(
+            FsqlCommand::parse("QUERY module_activity.** AFTER 1h"),
+            Some(FsqlCommand::Query)
+        );
+        assert_eq!(
+            FsqlCommand::parse("validate module_activity.**"),
+            Some(FsqlCommand::Validate)
+        );
+        assert_eq!(
+            FsqlCommand::parse("SUMMARIZE COUNT module_activity.uid"),
+            Some(FsqlCommand::Summarize)
+        );
+        assert_eq!(
+            FsqlCommand::parse("EXPLAIN CONNECTORS;"),
+            Some(FsqlCommand::ExplainConnectors)
+        );
+        assert_eq!(
+            FsqlCommand::parse("explain version"),
+            Some(FsqlCommand::ExplainVersion)
+        );
+        assert_eq!(
+            FsqlCommand::parse("EXPLAIN SCHEMA module_activity"),
+            Some(FsqlCommand::ExplainSchema)
+        );
+        assert_eq!(
+            FsqlCommand::parse("EXPLAIN GRAPHQL QUERY module_activity.**"),
+            Some(FsqlCommand::ExplainGraphql)
+        );
+        assert_eq!(
+            FsqlCommand::parse("EXPLAIN ATTRIBUTES module_activity.*"),
+            Some(FsqlCommand::ExplainAttributes)
+        );
+        assert_eq!(
+            FsqlCommand::parse("EXPLAIN QUERY module_activity.**"),
+            Some(FsqlCommand::Explain)
+        );
+    }
+
+    #[test]
+    fn test_parse_multiline_input() {
+        assert_eq!(
+            FsqlCommand::parse("QUERY\n  module_activity.**\nAFTER 1h;"),
+            Some(FsqlCommand::Query)
+        );
+        assert_eq!(
+            FsqlCommand::parse("EXPLAIN\nSCHEMA\tmodule_activity"),
+            Some(FsqlCommand::ExplainSchema)
+        );
+    }
+
+    #[test]
+    fn test_parse_invalid_commands() {
+        assert_eq!(FsqlCommand::parse(""), None);
+        assert_eq!(FsqlCommand::parse("QUERY"), None);
+        assert_eq!(FsqlCommand::parse("QUERY ;"), None);
+        assert_eq!(FsqlCommand::parse("EXPLAIN"), None);
+        assert_eq!(FsqlCommand::parse("EXPLAIN SCHEMA"), None);
+        assert_eq!(FsqlCommand::parse("SELECT * FROM events"), None);
+    }
+}
@@ -0,0 +1,295 @@
+//! Tab completion of REPL input
+//!
+//! Completion works on the word under the cursor. Which words are offered
+//! depends on what comes before it: the first word of a line can be an FSQL
+//! verb or a REPL command, the word after `EXPLAIN` a sub-verb, and anything
+//! later in a statement a clause keyword, event class or attribute path.
+//! Classes and attributes come from a [`SchemaCache`]. The line is split with
+//! the FSQL [`lexer`], so nothing is offered inside a string literal or
+//! comment.
+
+use crate::fsql::lexer::{self, TokenKind};
+use crate::output::OutputFormat;
+use crate::schema::SchemaCache;
+use clap::ValueEnum;
+
+/// FSQL verbs that start a statement
+pub const VERBS: &[&str] = &["QUERY", "EXPLAIN", "VALIDATE", "SUMMARIZE"];
+
+/// Words that can follow `EXPLAIN`
+pub const SUB_VERBS: &[&str] = &["CONNECTORS", "SCHEMA", "GRAPHQL", "ATTRIBUTES", "VERSION"];
+
+/// Keywords that can appear in the body of a statement
+pub const CLAUSES: &[&str] = &["WITH", "AFTER", "BEFORE", "FROM", "AND", "OR"];
+
+/// Backslash meta-commands understood by the REPL
+pub const META_COMMANDS: &[&str] = &[
+    "\\format",
+    "\\columns",
+    "\\o",
+    "\\pager",
+    "\\view",
+    "\\edit",
+    "\\set",
+    "\\unset",
+    "\\save",
+    "\\load",
+    "\\queries",
+    "\\whoami",
+    "\\reset",
+];
+
+/// Plain word commands understood by the REPL
+pub const REPL_COMMANDS: &[&str] = &["help", "clear", "exit"];
+
+/// Whether a character separates the words that are completed
+fn is_separator(c: char) -> bool {
+    c.is_whitespace() || matches!(c, '(' | ')' | ',' | '=' | '!' | '<' | '>')
+}
+
+/// Find the start of the word that ends at `pos`
+pub fn word_start(line: &str, pos: usize) -> usize {
+    line[..pos]
+        .char_indices()
+        .rev()
+        .find(|(_, c)| is_separator(*c))
+        .map(|(i, c)| i + c.len_utf8())
+        .unwrap_or(0)
+}
+
+/// Keywords matching a prefix, in the case the user is typing in
+///
+/// A prefix that is entirely lowercase gets lowercase keywords; anything
+/// else gets them in upper case.
+fn keywords(words: &[&str], prefix: &str) -> Vec<String> {
+    let upper = prefix.to_uppercase();
+    let lowercase = !prefix.is_empty() && prefix == prefix.to_lowercase();
+    words
+        .iter()
+        .filter(|word| word.starts_with(&upper))
+        .map(|word| {
+            if lowercase {
+                word.to_lowercase()
+            } else {
+                word.to_string()
+            }
+        })
+        .collect()
+}
+
+/// Words matching a prefix exactly, ignoring case
+fn matching(words: impl IntoIterator<Item = String>, prefix: &str) -> Vec<String> {
+    let prefix = prefix.to_lowercase();
+    words
+        .into_iter()
+        .filter(|word| word.to_lowercase().starts_with(&prefix))
+        .collect()
+}
+
+/// Event classes or attribute paths matching a partial path
+///
+/// A path with no dots completes to a class. Otherwise the last segment is
+/// completed from the attributes beneath the rest of the path, along with the
+/// `*` and `**` wildcards when the path names an object.
+fn attributes(schema: &mut SchemaCache, prefix: &str) -> Vec<String> {
+    let starts_with =
+        |name: &String, prefix: &str| name.to_lowercase().starts_with(&prefix.to_lowercase());
+    let Some((parent, last)) = prefix.rsplit_once('.') else {
+        return schema
+            .children("")
+            .into_iter()
+            .filter(|class| starts_with(class, prefix))
+            .collect();
+    };
+
+    let children = schema.children(parent);
+    let wildcards = if children.is_empty() {
+        Vec::new()
+    } else {
+        vec!["*".to_string(), "**".to_string()]
+    };
+    children
+        .into_iter()
+        .chain(wildcards)
+        .filter(|name| starts_with(name, last))
+        .map(|name| format!("{}.{}", parent, name))
+        .collect()
+}
+
+/// Complete the word ending at `pos`
+///
+/// Returns the start of the word being replaced and the candidates for it.
+/// An empty list means there's nothing to offer; arguments to `\o` are left
+/// to filename completion. Without a schema, only keywords are completed.
+pub fn complete(line: &str, pos: usize, schema: Option<&mut SchemaCache>) -> (usize, Vec<String>) {
+    let start = word_start(line, pos);
+    let prefix = &line[start..pos];
+    let tokens = lexer::tokenize(&line[..pos]);
+
+    // Nothing is offered inside a string literal or comment
+    let open = tokens.last().is_some_and(|token| {
+        let text = token.text(line);
+        match token.kind {
+            TokenKind::Unterminated => true,
+            TokenKind::Comment => text.starts_with("--") || !text[2..].ends_with("*/"),
+            _ => false,
+        }
+    });
+    if open {
+        return (start, Vec::new());
+    }
+
+    let previous: Vec<&str> = tokens
+        .iter()
+        .filter(|token| {
+            token.span.end <= start
+                && matches!(
+                    token.kind,
+                    TokenKind::Word | TokenKind::Path | TokenKind::Meta
+                )
+        })
+        .map(|token| token.text(line))
+        .collect();
+
+    let candidates = match previous.as_slice() {
+        [] => {
+            if prefix.starts_with('\\') {
+                matching(META_COMMANDS.iter().map(|c| c.to_string()), prefix)
+            } else {
+                let mut candidates = keywords(VERBS, prefix);
+                candidates.extend(matching(
+                    REPL_COMMANDS.iter().map(|c| c.to_string()),
+                    prefix,
+                ));
+                candidates
+            }
+        }
+        [command] if command.eq_ignore_ascii_case("\\format") => matching(
+            OutputFormat::value_variants()
+                .iter()
+                .map(|format| format.to_string()),
+            prefix,
+        ),
+        [command, ..] if command.starts_with('\\') => Vec::new(),
+        [verb] if verb.eq_ignore_ascii_case("EXPLAIN") => {
+            let mut candidates = keywords(SUB_VERBS, prefix);
+            candidates.extend(keywords(VERBS, prefix));
+            candidates
+        }
+        [first, second] if first.eq_ignore_ascii_case("EXPLAIN") && is_verb(second) => {
+            keywords(VERBS, prefix)
+        }
+        // Attribute paths are never keywords
+        _ if prefix.contains('.') => schema
+            .map(|schema| attributes(schema, prefix))
+            .unwrap_or_default(),
+        _ => {
+            let mut candidates = keywords(CLAUSES, prefix);
+            if let Some(schema) = schema {
+                candidates.extend(attributes(schema, prefix));
+            }
+            candidates
+        }
+    };
+    (start, candidates)
+}
+
+/// Whether a word is one of the EXPLAIN sub-verbs that takes a statement
+fn is_verb(word: &str) -> bool {
+    ["GRAPHQL", "ATTRIBUTES"]
+        .iter()
+        .any(|verb| word.eq_ignore_ascii_case(verb))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidates(line: &str) -> Vec<String> {
+        complete(line, line.len(), None).1
+    }
+
+    fn schema_candidates(line: &str) -> Vec<String> {
+        let mut schema = SchemaCache::new(None);
+        let response = serde_json::from_str(
+            r#"{"command": "EXPLAIN SCHEMA", "schema": {
+                "module_activity": {"activity_id": "integer", "actor": {"process": {"pid": "integer"}}},
+                "file_activity": {"actor": {}}
+            }}"#,
+        )
+        .unwrap();
+        schema.merge("", &response);
+        complete(line, line.len(), Some(&mut schema)).1
+    }
+
+    #[test]
+    fn test_complete_first_word() {
+        assert_eq!(candidates("Q"), vec!["QUERY"]);
+        assert_eq!(candidates("ex"), vec!["explain", "exit"]);
+        assert_eq!(candidates("\\co"), vec!["\\columns"]);
+        assert_eq!(candidates("  va"), vec!["validate"]);
+        assert_eq!(candidates("").len(), VERBS.len() + REPL_COMMANDS.len());
+    }
+
+    #[test]
+    fn test_complete_after_explain() {
+        assert_eq!(candidates("EXPLAIN S"), vec!["SCHEMA", "SUMMARIZE"]);
+        assert_eq!(candidates("explain ver"), vec!["version"]);
+        assert_eq!(candidates("EXPLAIN GRAPHQL Q"), vec!["QUERY"]);
+    }
+
+    #[test]
+    fn test_complete_clauses() {
+        assert_eq!(
+            candidates("QUERY module_activity.** A"),
+            vec!["AFTER", "AND"]
+        );
+        assert_eq!(candidates("query x.* with x.a = 1 o"), vec!["or"]);
+        assert_eq!(
+            complete("QUERY a.** WITH x = 1 B", 23, None),
+            (22, vec!["BEFORE".to_string()])
+        );
+        assert!(candidates("QUERY a.** WITH x = 1 Z").is_empty());
+        assert!(candidates("QUERY a.** WITH x = 'A").is_empty());
+        assert!(candidates("QUERY a.** -- A").is_empty());
+        assert!(candidates("QUERY a.** /* A").is_empty());
+        assert_eq!(candidates("QUERY a.** /**/ A"), vec!["AFTER", "AND"]);
+        assert_eq!(
+            candidates("QUERY a.** WITH x = \"AND\" A"),
+            vec!["AFTER", "AND"]
+        );
+    }
+
+    #[test]
+    fn test_complete_attributes() {
+        assert_eq!(schema_candidates("QUERY mod"), vec!["module_activity"]);
+        assert_eq!(
+            schema_candidates("QUERY module_activity.a"),
+            vec!["module_activity.activity_id", "module_activity.actor"]
+        );
+        assert_eq!(
+            schema_candidates("QUERY module_activity.actor.process."),
+            vec![
+                "module_activity.actor.process.pid",
+                "module_activity.actor.process.*",
+                "module_activity.actor.process.**",
+            ]
+        );
+        assert_eq!(
+            schema_candidates("QUERY module_activity.** WITH module_activity.actor.process.p"),
+            vec!["module_activity.actor.process.pid"]
+        );
+        assert_eq!(
+            schema_candidates("QUERY module_activity.* F"),
+            vec!["FROM", "file_activity"]
+        );
+        assert!(schema_candidates("QUERY module_activity.activity_id.").is_empty());
+        assert!(schema_candidates("QUERY unknown.").is_empty());
+    }
+
+    #[test]
+    fn test_complete_meta_arguments() {
+        assert_eq!(candidates("\\format c"), vec!["csv"]);
+        assert!(candidates("\\columns ti").is_empty());
+    }
+}
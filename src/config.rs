@@ -1,7 +1,13 @@
+use crate::lint;
+use crate::output::OutputFormat;
+use crate::pager::PagerMode;
+use crate::secrets;
+use clap::ValueEnum;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
-use std::path::PathBuf;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
 
 /// Get the OS-appropriate directory for storing fsqlctl data
 /// This follows XDG standards on Linux and equivalent on other platforms
@@ -20,19 +26,268 @@ pub fn get_config_dir() -> PathBuf {
 }
 
 /// Configuration structure for fsqlctl
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Config {
+    /// Name of the profile to use when none is given on the command line
+    #[serde(
+        rename = "default-profile",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub default_profile: Option<String>,
+
+    /// Store tokens encrypted with a passphrase rather than in plaintext
+    #[serde(
+        rename = "encrypt-tokens",
+        default,
+        skip_serializing_if = "std::ops::Not::not"
+    )]
+    pub encrypt_tokens: bool,
+
     /// Mapping of hostnames to their corresponding API keys/tokens
-    #[serde(rename = "api-keys")]
+    #[serde(rename = "api-keys", default)]
     pub api_keys: HashMap<String, String>,
+
+    /// Named connection profiles, from `[profiles.<name>]` sections
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub profiles: BTreeMap<String, Profile>,
+
+    /// When long REPL output goes through the pager
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pager: Option<PagerMode>,
+
+    /// Settings for `fsqlctl lint`, from the `[lint]` section
+    #[serde(default, skip_serializing_if = "LintConfig::is_empty")]
+    pub lint: LintConfig,
+}
+
+/// Settings for the `fsqlctl lint` rules
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct LintConfig {
+    /// Rules turned on or off by name; rules that aren't listed are on
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub rules: BTreeMap<String, bool>,
+    /// Event classes too large to select everything from with `**`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub huge_classes: Vec<String>,
+    /// Attributes whose values are usually lower case, replacing the defaults
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lowercase_fields: Option<Vec<String>>,
+    /// Schema file to check attribute paths against
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schema: Option<PathBuf>,
+}
+
+impl LintConfig {
+    fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Whether a rule is turned on
+    pub fn enabled(&self, rule: &str) -> bool {
+        self.rules.get(rule).copied().unwrap_or(true)
+    }
+}
+
+/// A named set of connection settings
+///
+/// Every field is optional; anything left out falls back to the command line
+/// defaults. Command line arguments always take precedence over a profile.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Profile {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub host: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub port: Option<u16>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+    /// Default format for QUERY results
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output: Option<OutputFormat>,
+    /// Request timeout in seconds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<u64>,
+}
+
+/// The settings of a profile, in the order they're listed
+const PROFILE_FIELDS: &[&str] = &["host", "port", "path", "token", "output", "timeout"];
+
+impl Profile {
+    /// Get a setting as text
+    fn get(&self, field: &str) -> Option<String> {
+        match field {
+            "host" => self.host.clone(),
+            "port" => self.port.map(|port| port.to_string()),
+            "path" => self.path.clone(),
+            "token" => self.token.clone(),
+            "output" => self.output.map(|format| format.to_string()),
+            "timeout" => self.timeout.map(|timeout| timeout.to_string()),
+            _ => None,
+        }
+    }
+
+    /// Parse and store a setting
+    fn set(&mut self, field: &str, value: &str) -> Result<(), String> {
+        let invalid = |e: &dyn std::fmt::Display| format!("Invalid {} '{}': {}", field, value, e);
+        match field {
+            "host" => self.host = Some(value.to_string()),
+            "port" => self.port = Some(value.parse().map_err(|e| invalid(&e))?),
+            "path" => self.path = Some(value.to_string()),
+            "token" => self.token = Some(value.to_string()),
+            "output" => {
+                self.output = Some(OutputFormat::from_str(value, true).map_err(|e| invalid(&e))?)
+            }
+            "timeout" => self.timeout = Some(value.parse().map_err(|e| invalid(&e))?),
+            _ => unreachable!("profile fields are checked by ConfigKey::parse"),
+        }
+        Ok(())
+    }
+
+    /// Clear a setting, returning whether it was set
+    fn unset(&mut self, field: &str) -> bool {
+        match field {
+            "host" => self.host.take().is_some(),
+            "port" => self.port.take().is_some(),
+            "path" => self.path.take().is_some(),
+            "token" => self.token.take().is_some(),
+            "output" => self.output.take().is_some(),
+            "timeout" => self.timeout.take().is_some(),
+            _ => false,
+        }
+    }
+}
+
+/// A dotted key naming a single setting in the config file
+#[derive(Debug, PartialEq)]
+enum ConfigKey<'a> {
+    /// `default-profile`
+    DefaultProfile,
+    /// `encrypt-tokens`
+    EncryptTokens,
+    /// `pager`
+    Pager,
+    /// `api-keys.<host>`
+    ApiKey(&'a str),
+    /// `profiles.<name>.<field>`
+    Profile(&'a str, &'a str),
+    /// `lint.rules.<rule>`
+    LintRule(&'a str),
+}
+
+impl<'a> ConfigKey<'a> {
+    fn parse(key: &'a str) -> Result<Self, String> {
+        if key == "default-profile" {
+            return Ok(Self::DefaultProfile);
+        }
+        if key == "encrypt-tokens" {
+            return Ok(Self::EncryptTokens);
+        }
+        if key == "pager" {
+            return Ok(Self::Pager);
+        }
+        // Hostnames contain dots, so everything after the prefix is the host
+        if let Some(host) = key
+            .strip_prefix("api-keys.")
+            .filter(|host| !host.is_empty())
+        {
+            return Ok(Self::ApiKey(host));
+        }
+        if let Some((name, field)) = key
+            .strip_prefix("profiles.")
+            .and_then(|rest| rest.rsplit_once('.'))
+            .filter(|(name, _)| !name.is_empty())
+        {
+            return if PROFILE_FIELDS.contains(&field) {
+                Ok(Self::Profile(name, field))
+            } else {
+                Err(format!(
+                    "Unknown profile setting '{}' (expected one of: {})",
+                    field,
+                    PROFILE_FIELDS.join(", ")
+                ))
+            };
+        }
+        if let Some(rule) = key.strip_prefix("lint.rules.") {
+            return if lint::RULES.contains(&rule) {
+                Ok(Self::LintRule(rule))
+            } else {
+                Err(format!(
+                    "Unknown lint rule '{}' (expected one of: {})",
+                    rule,
+                    lint::RULES.join(", ")
+                ))
+            };
+        }
+        Err(format!(
+            "Unknown config key '{}' (expected default-profile, encrypt-tokens, pager, api-keys.<host>, profiles.<name>.<setting> or lint.rules.<rule>)",
+            key
+        ))
+    }
+}
+
+/// Whether a dotted key names a token, whose value should be masked
+pub fn is_token_key(key: &str) -> bool {
+    matches!(
+        ConfigKey::parse(key),
+        Ok(ConfigKey::ApiKey(_) | ConfigKey::Profile(_, "token"))
+    )
+}
+
+/// Hide most of a token so it can be shown on screen
+pub fn mask_token(token: &str) -> String {
+    let chars: Vec<char> = token.chars().collect();
+    if chars.len() <= 12 {
+        return "*".repeat(8);
+    }
+    let start: String = chars[..4].iter().collect();
+    let end: String = chars[chars.len() - 4..].iter().collect();
+    format!("{}…{}", start, end)
 }
 
-impl Default for Config {
-    fn default() -> Self {
-        Self {
-            api_keys: HashMap::new(),
+fn display_token(token: &str, show_tokens: bool) -> String {
+    if show_tokens {
+        token.to_string()
+    } else if secrets::is_encrypted(token) {
+        "(encrypted)".to_string()
+    } else {
+        mask_token(token)
+    }
+}
+
+/// Write a file that only the current user can read or write
+///
+/// On Unix the file is created with mode 0600, and an existing file has its
+/// permissions tightened before it is overwritten.
+fn write_private(path: &Path, contents: &str) -> io::Result<()> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+        options.mode(0o600);
+        if path.exists() {
+            fs::set_permissions(path, fs::Permissions::from_mode(0o600))?;
         }
     }
+    let mut file = options.open(path)?;
+    file.write_all(contents.as_bytes())
+}
+
+/// Get the permission bits of a file if it can be read by other users
+#[cfg(unix)]
+fn insecure_mode(path: &Path) -> Option<u32> {
+    use std::os::unix::fs::PermissionsExt;
+    let mode = fs::metadata(path).ok()?.permissions().mode() & 0o777;
+    (mode & 0o077 != 0).then_some(mode)
+}
+
+#[cfg(not(unix))]
+fn insecure_mode(_path: &Path) -> Option<u32> {
+    None
 }
 
 impl Config {
@@ -60,11 +315,36 @@ impl Config {
         fs::create_dir_all(&config_dir)?;
 
         let config_content = toml::to_string_pretty(self)?;
-        fs::write(&config_path, config_content)?;
+        write_private(&config_path, &config_content)?;
 
         Ok(())
     }
 
+    /// Warn about a config file that other users can read
+    ///
+    /// Returns a message to show the user if the file's permissions allow
+    /// group or world access.
+    pub fn permission_warning() -> Option<String> {
+        let path = Self::get_config_path();
+        insecure_mode(&path).map(|mode| {
+            format!(
+                "{} is readable by other users (mode {:o}) - run `chmod 600 {}` to protect your tokens",
+                path.display(),
+                mode,
+                path.display()
+            )
+        })
+    }
+
+    /// Every stored token, from both the api-keys table and profiles
+    pub fn tokens_mut(&mut self) -> impl Iterator<Item = &mut String> {
+        self.api_keys.values_mut().chain(
+            self.profiles
+                .values_mut()
+                .filter_map(|profile| profile.token.as_mut()),
+        )
+    }
+
     /// Get the stored API key/token for a specific host
     pub fn get_token(&self, host: &str) -> Option<&String> {
         self.api_keys.get(host)
@@ -75,6 +355,144 @@ impl Config {
         self.api_keys.insert(host.to_string(), token.to_string());
     }
 
+    /// Get the profile to use, given the name requested on the command line
+    ///
+    /// Falls back to the `default-profile` when no name is given. Returns an
+    /// error if the selected profile doesn't exist.
+    pub fn select_profile(&self, name: Option<&str>) -> Result<Option<&Profile>, String> {
+        let Some(name) = name.or(self.default_profile.as_deref()) else {
+            return Ok(None);
+        };
+        match self.profiles.get(name) {
+            Some(profile) => Ok(Some(profile)),
+            None => Err(format!(
+                "No profile named '{}' in {}",
+                name,
+                Self::get_config_location()
+            )),
+        }
+    }
+
+    /// List every setting as a dotted key and its value
+    ///
+    /// API keys come first, sorted by host, followed by each profile's
+    /// settings. Token values are masked unless `show_tokens` is set.
+    pub fn entries(&self, show_tokens: bool) -> Vec<(String, String)> {
+        let mut entries = Vec::new();
+        if let Some(name) = &self.default_profile {
+            entries.push(("default-profile".to_string(), name.clone()));
+        }
+        if self.encrypt_tokens {
+            entries.push(("encrypt-tokens".to_string(), "true".to_string()));
+        }
+        if let Some(mode) = self.pager {
+            entries.push(("pager".to_string(), mode.to_string()));
+        }
+
+        let mut hosts: Vec<&String> = self.api_keys.keys().collect();
+        hosts.sort();
+        for host in hosts {
+            entries.push((
+                format!("api-keys.{}", host),
+                display_token(&self.api_keys[host], show_tokens),
+            ));
+        }
+
+        for (name, profile) in &self.profiles {
+            for field in PROFILE_FIELDS {
+                if let Some(value) = profile.get(field) {
+                    let value = if *field == "token" {
+                        display_token(&value, show_tokens)
+                    } else {
+                        value
+                    };
+                    entries.push((format!("profiles.{}.{}", name, field), value));
+                }
+            }
+        }
+
+        for (rule, enabled) in &self.lint.rules {
+            entries.push((format!("lint.rules.{}", rule), enabled.to_string()));
+        }
+        entries
+    }
+
+    /// Get the value of a setting by its dotted key
+    ///
+    /// Returns `Ok(None)` if the key is valid but not set.
+    pub fn get(&self, key: &str) -> Result<Option<String>, String> {
+        match ConfigKey::parse(key)? {
+            ConfigKey::DefaultProfile => Ok(self.default_profile.clone()),
+            ConfigKey::EncryptTokens => Ok(self.encrypt_tokens.then(|| "true".to_string())),
+            ConfigKey::Pager => Ok(self.pager.map(|mode| mode.to_string())),
+            ConfigKey::ApiKey(host) => Ok(self.api_keys.get(host).cloned()),
+            ConfigKey::Profile(name, field) => Ok(self
+                .profiles
+                .get(name)
+                .and_then(|profile| profile.get(field))),
+            ConfigKey::LintRule(rule) => {
+                Ok(self.lint.rules.get(rule).map(|enabled| enabled.to_string()))
+            }
+        }
+    }
+
+    /// Set a setting by its dotted key
+    ///
+    /// Setting a field of a profile that doesn't exist yet creates it.
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match ConfigKey::parse(key)? {
+            ConfigKey::DefaultProfile => self.default_profile = Some(value.to_string()),
+            ConfigKey::EncryptTokens => {
+                self.encrypt_tokens = value
+                    .parse()
+                    .map_err(|e| format!("Invalid encrypt-tokens '{}': {}", value, e))?
+            }
+            ConfigKey::Pager => {
+                self.pager =
+                    Some(PagerMode::from_str(value, true).map_err(|_| {
+                        format!("Invalid pager '{}' (expected on, off or auto)", value)
+                    })?)
+            }
+            ConfigKey::ApiKey(host) => self.set_token(host, value),
+            ConfigKey::Profile(name, field) => self
+                .profiles
+                .entry(name.to_string())
+                .or_default()
+                .set(field, value)?,
+            ConfigKey::LintRule(rule) => {
+                let enabled = value
+                    .parse()
+                    .map_err(|e| format!("Invalid lint.rules.{} '{}': {}", rule, value, e))?;
+                self.lint.rules.insert(rule.to_string(), enabled);
+            }
+        }
+        Ok(())
+    }
+
+    /// Remove a setting by its dotted key
+    ///
+    /// `profiles.<name>` removes a whole profile. Returns whether anything
+    /// was removed.
+    pub fn unset(&mut self, key: &str) -> Result<bool, String> {
+        if let Some(name) = key
+            .strip_prefix("profiles.")
+            .filter(|name| !name.is_empty() && self.profiles.contains_key(*name))
+        {
+            return Ok(self.profiles.remove(name).is_some());
+        }
+        match ConfigKey::parse(key)? {
+            ConfigKey::DefaultProfile => Ok(self.default_profile.take().is_some()),
+            ConfigKey::EncryptTokens => Ok(std::mem::take(&mut self.encrypt_tokens)),
+            ConfigKey::Pager => Ok(self.pager.take().is_some()),
+            ConfigKey::ApiKey(host) => Ok(self.api_keys.remove(host).is_some()),
+            ConfigKey::Profile(name, field) => Ok(self
+                .profiles
+                .get_mut(name)
+                .is_some_and(|profile| profile.unset(field))),
+            ConfigKey::LintRule(rule) => Ok(self.lint.rules.remove(rule).is_some()),
+        }
+    }
+
     /// Get the path to the config file
     pub fn get_config_path() -> PathBuf {
         get_config_dir().join("config.toml")
@@ -128,6 +546,198 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_profiles() {
+        let config: Config = toml::from_str(
+            r#"
+            default-profile = "prod"
+
+            [profiles.prod]
+            host = "api.query.ai"
+            token = "prod-token"
+            output = "table"
+            timeout = 120
+
+            [profiles.local]
+            host = "localhost"
+            port = 8443
+            path = "fsql"
+            "#,
+        )
+        .unwrap();
+
+        assert!(config.api_keys.is_empty());
+
+        let prod = config.select_profile(None).unwrap().unwrap();
+        assert_eq!(prod.token.as_deref(), Some("prod-token"));
+        assert_eq!(prod.output, Some(OutputFormat::Table));
+        assert_eq!(prod.timeout, Some(120));
+
+        let local = config.select_profile(Some("local")).unwrap().unwrap();
+        assert_eq!(local.port, Some(8443));
+        assert_eq!(local.token, None);
+
+        assert!(config.select_profile(Some("missing")).is_err());
+        assert_eq!(Config::default().select_profile(None).unwrap(), None);
+
+        // Profiles survive a round trip through the config file
+        let reloaded: Config = toml::from_str(&toml::to_string_pretty(&config).unwrap()).unwrap();
+        assert_eq!(reloaded.default_profile.as_deref(), Some("prod"));
+        assert_eq!(reloaded.profiles, config.profiles);
+    }
+
+    #[test]
+    fn test_dotted_keys() {
+        let mut config = Config::default();
+        config
+            .set("api-keys.api.query.ai", "eyJhbGciOiJIUzI1NiJ9.e30.sig")
+            .unwrap();
+        config.set("profiles.dev.port", "8443").unwrap();
+        config.set("profiles.dev.output", "CSV").unwrap();
+        config.set("default-profile", "dev").unwrap();
+
+        assert_eq!(
+            config.get("api-keys.api.query.ai").unwrap().as_deref(),
+            Some("eyJhbGciOiJIUzI1NiJ9.e30.sig")
+        );
+        assert_eq!(config.profiles["dev"].port, Some(8443));
+        assert_eq!(config.profiles["dev"].output, Some(OutputFormat::Csv));
+        assert_eq!(config.get("profiles.dev.host").unwrap(), None);
+        assert_eq!(config.get("api-keys.other").unwrap(), None);
+
+        assert!(config.set("profiles.dev.port", "http").is_err());
+        assert!(config.set("profiles.dev.colour", "red").is_err());
+        assert!(config.get("nonsense").is_err());
+        assert!(config.get("api-keys.").is_err());
+
+        assert_eq!(
+            config.entries(false),
+            vec![
+                ("default-profile".to_string(), "dev".to_string()),
+                ("api-keys.api.query.ai".to_string(), "eyJh….sig".to_string()),
+                ("profiles.dev.port".to_string(), "8443".to_string()),
+                ("profiles.dev.output".to_string(), "csv".to_string()),
+            ]
+        );
+
+        assert_eq!(config.unset("profiles.dev.port"), Ok(true));
+        assert_eq!(config.unset("profiles.dev.port"), Ok(false));
+        assert_eq!(config.unset("profiles.dev"), Ok(true));
+        assert!(config.profiles.is_empty());
+        assert_eq!(config.unset("api-keys.api.query.ai"), Ok(true));
+        assert_eq!(config.unset("default-profile"), Ok(true));
+    }
+
+    #[test]
+    fn test_pager_key() {
+        let mut config = Config::default();
+        assert_eq!(config.get("pager").unwrap(), None);
+        config.set("pager", "OFF").unwrap();
+        assert_eq!(config.pager, Some(PagerMode::Off));
+        assert_eq!(config.get("pager").unwrap(), Some("off".to_string()));
+        assert!(
+            toml::to_string(&config)
+                .unwrap()
+                .contains("pager = \"off\"")
+        );
+        assert!(config.set("pager", "sometimes").is_err());
+        assert_eq!(config.unset("pager"), Ok(true));
+        assert!(config.entries(false).is_empty());
+    }
+
+    #[test]
+    fn test_encrypt_tokens_key() {
+        let mut config = Config::default();
+        assert_eq!(config.get("encrypt-tokens").unwrap(), None);
+        config.set("encrypt-tokens", "true").unwrap();
+        assert!(config.encrypt_tokens);
+        assert!(config.set("encrypt-tokens", "yes").is_err());
+
+        config.set_token("api.query.ai", "enc:v1:AAAA");
+        assert_eq!(
+            config.entries(false),
+            vec![
+                ("encrypt-tokens".to_string(), "true".to_string()),
+                (
+                    "api-keys.api.query.ai".to_string(),
+                    "(encrypted)".to_string()
+                ),
+            ]
+        );
+        assert!(
+            toml::to_string(&config)
+                .unwrap()
+                .contains("encrypt-tokens = true")
+        );
+        assert_eq!(config.unset("encrypt-tokens"), Ok(true));
+        assert!(!toml::to_string(&config).unwrap().contains("encrypt-tokens"));
+    }
+
+    #[test]
+    fn test_lint_settings() {
+        let mut config: Config = toml::from_str(
+            r#"
+            [lint]
+            huge-classes = ["network_activity"]
+            schema = "/tmp/schema.json"
+
+            [lint.rules]
+            duplicate-condition = false
+            "#,
+        )
+        .unwrap();
+        assert!(!config.lint.enabled("duplicate-condition"));
+        assert!(config.lint.enabled("missing-time-bound"));
+        assert_eq!(config.lint.huge_classes, vec!["network_activity"]);
+
+        config
+            .set("lint.rules.missing-time-bound", "false")
+            .unwrap();
+        assert_eq!(
+            config
+                .get("lint.rules.missing-time-bound")
+                .unwrap()
+                .as_deref(),
+            Some("false")
+        );
+        assert!(config.set("lint.rules.missing-time-bound", "off").is_err());
+        assert!(config.set("lint.rules.no-such-rule", "true").is_err());
+        assert_eq!(config.unset("lint.rules.duplicate-condition"), Ok(true));
+        assert!(config.lint.enabled("duplicate-condition"));
+        assert!(
+            !toml::to_string(&Config::default())
+                .unwrap()
+                .contains("[lint]")
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_write_private_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        write_private(&path, "a = 1\n").unwrap();
+        assert_eq!(insecure_mode(&path), None);
+
+        // An existing world readable file is tightened when it's rewritten
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+        assert_eq!(insecure_mode(&path), Some(0o644));
+        write_private(&path, "a = 2\n").unwrap();
+        assert_eq!(insecure_mode(&path), None);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "a = 2\n");
+    }
+
+    #[test]
+    fn test_mask_token() {
+        assert_eq!(mask_token("short"), "********");
+        assert_eq!(mask_token("abcdefghijklmnop"), "abcd…mnop");
+        assert!(is_token_key("api-keys.localhost"));
+        assert!(is_token_key("profiles.prod.token"));
+        assert!(!is_token_key("profiles.prod.host"));
+    }
+
     #[test]
     fn test_serialization() {
         let mut config = Config::default();
@@ -0,0 +1,62 @@
+//! Opening files in the user's text editor
+
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+use std::process::Command;
+
+/// The editor to use when neither `$VISUAL` nor `$EDITOR` is set
+#[cfg(windows)]
+const DEFAULT_EDITOR: &str = "notepad";
+#[cfg(not(windows))]
+const DEFAULT_EDITOR: &str = "vi";
+
+/// Get the editor command from `$VISUAL` or `$EDITOR`
+///
+/// The variables may include arguments (e.g. `code --wait`), so the command is
+/// returned split on whitespace.
+fn editor_command() -> Vec<String> {
+    ["VISUAL", "EDITOR"]
+        .iter()
+        .filter_map(|name| std::env::var(name).ok())
+        .map(|value| {
+            value
+                .split_whitespace()
+                .map(str::to_string)
+                .collect::<Vec<_>>()
+        })
+        .find(|command| !command.is_empty())
+        .unwrap_or_else(|| vec![DEFAULT_EDITOR.to_string()])
+}
+
+/// Open a file in the user's editor and wait for it to exit
+pub fn edit_file(path: &Path) -> io::Result<()> {
+    let command = editor_command();
+    let status = Command::new(&command[0])
+        .args(&command[1..])
+        .arg(path)
+        .status()
+        .map_err(|e| io::Error::new(e.kind(), format!("Failed to run '{}': {}", command[0], e)))?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!(
+            "'{}' exited with {}",
+            command[0], status
+        )))
+    }
+}
+
+/// Edit text in the user's editor, through a temporary `.fsql` file
+///
+/// Returns the text as it was saved.
+pub fn edit_text(text: &str) -> io::Result<String> {
+    let mut file = tempfile::Builder::new()
+        .prefix("fsqlctl-")
+        .suffix(".fsql")
+        .tempfile()?;
+    writeln!(file, "{}", text)?;
+    file.flush()?;
+    edit_file(file.path())?;
+    fs::read_to_string(file.path())
+}
@@ -0,0 +1,190 @@
+//! The `fsqlctl fmt` subcommand
+//!
+//! Formats FSQL files in place with [`fsql::format`], or stdin to stdout
+//! when no files are given. Directories are searched for `.fsql` files. With
+//! `--check` nothing is written; the exit status says whether anything would
+//! change, for use in CI.
+
+use crate::fsql;
+use colored::Colorize;
+use std::fs;
+use std::io::{self, IsTerminal, Read, Write};
+use std::path::{Path, PathBuf};
+
+/// File extension of the files found in directories
+const EXTENSION: &str = "fsql";
+
+/// What happened to one input
+enum Outcome {
+    Unchanged,
+    Changed,
+    Failed,
+}
+
+/// Collect `.fsql` files beneath a directory, in a stable order
+fn find_files(directory: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    let mut entries = fs::read_dir(directory)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<io::Result<Vec<_>>>()?;
+    entries.sort();
+    for path in entries {
+        if path.is_dir() {
+            find_files(&path, files)?;
+        } else if path.extension().is_some_and(|ext| ext == EXTENSION) {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Expand directories in the paths given on the command line
+///
+/// Exits the process if a directory can't be read.
+pub fn expand_paths(paths: &[PathBuf]) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    for path in paths {
+        if !path.is_dir() {
+            files.push(path.clone());
+        } else if let Err(e) = find_files(path, &mut files) {
+            eprintln!(
+                "{}",
+                format!("❌ Failed to read {}: {}", path.display(), e).red()
+            );
+            std::process::exit(1);
+        }
+    }
+    files
+}
+
+/// Replace a file's contents without leaving it half written
+fn rewrite(path: &Path, contents: &str) -> io::Result<()> {
+    let directory = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let mut temp = tempfile::NamedTempFile::new_in(directory)?;
+    fs::set_permissions(temp.path(), fs::metadata(path)?.permissions())?;
+    temp.write_all(contents.as_bytes())?;
+    temp.as_file().sync_all()?;
+    temp.persist(path)?;
+    Ok(())
+}
+
+/// Format one file, rewriting it unless only checking
+fn format_file(path: &Path, check: bool) -> Outcome {
+    let source = match fs::read_to_string(path) {
+        Ok(source) => source,
+        Err(e) => {
+            eprintln!(
+                "{}",
+                format!("❌ Failed to read {}: {}", path.display(), e).red()
+            );
+            return Outcome::Failed;
+        }
+    };
+    let formatted = match fsql::format::format(&source) {
+        Ok(formatted) => formatted + "\n",
+        Err(e) => {
+            eprintln!(
+                "{}",
+                format!("❌ {}: {}", path.display(), e.render(&source)).red()
+            );
+            return Outcome::Failed;
+        }
+    };
+    if formatted == source {
+        return Outcome::Unchanged;
+    }
+    if check {
+        eprintln!("Would reformat {}", path.display());
+    } else if let Err(e) = rewrite(path, &formatted) {
+        eprintln!(
+            "{}",
+            format!("❌ Failed to write {}: {}", path.display(), e).red()
+        );
+        return Outcome::Failed;
+    } else {
+        eprintln!("Formatted {}", path.display());
+    }
+    Outcome::Changed
+}
+
+/// Format stdin to stdout
+fn format_stdin(check: bool) -> Outcome {
+    if io::stdin().is_terminal() {
+        eprintln!("{}", "❌ Give files to format or pipe FSQL to stdin".red());
+        return Outcome::Failed;
+    }
+    let mut source = String::new();
+    if let Err(e) = io::stdin().read_to_string(&mut source) {
+        eprintln!("Error reading from stdin: {}", e);
+        return Outcome::Failed;
+    }
+    match fsql::format::format(&source) {
+        Ok(formatted) if check => {
+            if formatted + "\n" == source {
+                Outcome::Unchanged
+            } else {
+                eprintln!("Would reformat stdin");
+                Outcome::Changed
+            }
+        }
+        Ok(formatted) => {
+            println!("{}", formatted);
+            Outcome::Unchanged
+        }
+        Err(e) => {
+            eprintln!("{}", e.render(&source).red());
+            Outcome::Failed
+        }
+    }
+}
+
+/// Run `fsqlctl fmt`, exiting with a non-zero status on failure
+///
+/// With `check`, files that would be reformatted also count as a failure.
+pub fn handle_fmt(paths: &[PathBuf], check: bool) {
+    let outcomes = if paths.is_empty() {
+        vec![format_stdin(check)]
+    } else {
+        expand_paths(paths)
+            .iter()
+            .map(|path| format_file(path, check))
+            .collect()
+    };
+
+    let failed = outcomes
+        .iter()
+        .filter(|outcome| matches!(outcome, Outcome::Failed))
+        .count();
+    let changed = outcomes
+        .iter()
+        .filter(|outcome| matches!(outcome, Outcome::Changed))
+        .count();
+    if failed > 0 || (check && changed > 0) {
+        std::process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_file() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("hunt.fsql");
+        fs::write(&path, "query a.* with a.ip = 10.0.0.1 and a.name = cmd.exe").unwrap();
+
+        assert!(matches!(format_file(&path, true), Outcome::Changed));
+        assert!(matches!(format_file(&path, false), Outcome::Changed));
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "QUERY a.*\n  WITH a.ip = 10.0.0.1\n   AND a.name = cmd.exe\n"
+        );
+        assert!(matches!(format_file(&path, true), Outcome::Unchanged));
+
+        fs::write(&path, "QUERY a.* WITH").unwrap();
+        assert!(matches!(format_file(&path, false), Outcome::Failed));
+    }
+}
@@ -0,0 +1,193 @@
+//! The syntax tree of an FSQL statement
+
+use super::Span;
+
+/// A complete FSQL command
+#[derive(Debug, Clone, PartialEq)]
+pub enum Statement {
+    /// `QUERY <selections> <clauses>`
+    Query(Query),
+    /// `VALIDATE <fsql>`
+    Validate(Box<Statement>),
+    /// `SUMMARIZE <operation> <selections> <clauses>`
+    Summarize(Summarize),
+    /// `EXPLAIN ...`
+    Explain(Explain),
+}
+
+/// The forms of `EXPLAIN`
+#[derive(Debug, Clone, PartialEq)]
+pub enum Explain {
+    Connectors,
+    Version,
+    /// `EXPLAIN SCHEMA <path>`
+    Schema(Path),
+    /// `EXPLAIN ATTRIBUTES <fsql>`
+    Attributes(Box<Statement>),
+    /// `EXPLAIN GRAPHQL <fsql>`
+    Graphql(Box<Statement>),
+    /// `EXPLAIN <fsql>`
+    Statement(Box<Statement>),
+}
+
+/// The attributes to fetch and the clauses that restrict them
+///
+/// Statements that wrap another, such as `VALIDATE`, accept a query without
+/// the `QUERY` keyword; `keyword` records whether it was written.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Query {
+    pub keyword: bool,
+    pub selections: Vec<Path>,
+    pub clauses: Vec<Clause>,
+    pub span: Span,
+}
+
+impl Query {
+    /// Whether the query has an `AFTER` or `BEFORE` clause
+    pub fn is_time_bounded(&self) -> bool {
+        self.clauses
+            .iter()
+            .any(|clause| matches!(clause, Clause::After(_) | Clause::Before(_)))
+    }
+}
+
+/// `SUMMARIZE <operation> ...`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Summarize {
+    /// Operation such as `COUNT`, as written
+    pub operation: String,
+    pub query: Query,
+}
+
+/// A clause following the selections of a query
+#[derive(Debug, Clone, PartialEq)]
+pub enum Clause {
+    /// `WITH <condition>`
+    With(Condition),
+    /// `FROM <connector>, ...`
+    From(Vec<Value>),
+    /// `AFTER <time>`
+    After(Value),
+    /// `BEFORE <time>`
+    Before(Value),
+}
+
+/// A boolean condition in a `WITH` clause
+#[derive(Debug, Clone, PartialEq)]
+pub enum Condition {
+    /// Two or more conditions joined by `AND`
+    And(Vec<Condition>),
+    /// Two or more conditions joined by `OR`
+    Or(Vec<Condition>),
+    Not(Box<Condition>),
+    /// A condition in parentheses
+    Group(Box<Condition>),
+    Comparison(Comparison),
+}
+
+impl Condition {
+    /// Every comparison within the condition, in order
+    pub fn comparisons(&self) -> Vec<&Comparison> {
+        match self {
+            Condition::And(conditions) | Condition::Or(conditions) => conditions
+                .iter()
+                .flat_map(|condition| condition.comparisons())
+                .collect(),
+            Condition::Not(condition) | Condition::Group(condition) => condition.comparisons(),
+            Condition::Comparison(comparison) => vec![comparison],
+        }
+    }
+}
+
+/// `<path> <operator> <value>`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Comparison {
+    pub path: Path,
+    pub operator: Operator,
+    pub value: Value,
+    pub span: Span,
+}
+
+/// Comparison operators
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operator {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    /// `~` - matches a pattern
+    Matches,
+    /// `!~` - doesn't match a pattern
+    NotMatches,
+    Contains,
+    StartsWith,
+    EndsWith,
+    /// `IN (...)`
+    In,
+}
+
+impl Operator {
+    /// Parse an operator token or word
+    pub fn parse(text: &str) -> Option<Self> {
+        Some(match text.to_uppercase().as_str() {
+            "=" => Self::Eq,
+            "!=" => Self::Ne,
+            "<" => Self::Lt,
+            "<=" => Self::Le,
+            ">" => Self::Gt,
+            ">=" => Self::Ge,
+            "~" => Self::Matches,
+            "!~" => Self::NotMatches,
+            "CONTAINS" => Self::Contains,
+            "STARTSWITH" => Self::StartsWith,
+            "ENDSWITH" => Self::EndsWith,
+            "IN" => Self::In,
+            _ => return None,
+        })
+    }
+
+    /// The operator as it's written in canonical form
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Eq => "=",
+            Self::Ne => "!=",
+            Self::Lt => "<",
+            Self::Le => "<=",
+            Self::Gt => ">",
+            Self::Ge => ">=",
+            Self::Matches => "~",
+            Self::NotMatches => "!~",
+            Self::Contains => "CONTAINS",
+            Self::StartsWith => "STARTSWITH",
+            Self::EndsWith => "ENDSWITH",
+            Self::In => "IN",
+        }
+    }
+}
+
+/// A dotted attribute path, possibly with wildcards
+#[derive(Debug, Clone, PartialEq)]
+pub struct Path {
+    pub text: String,
+    pub span: Span,
+}
+
+/// A literal value
+///
+/// Values keep the text they were written with, so they can be shown again
+/// exactly as they were.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    /// A quoted string, including the quotes
+    String(String),
+    Number(String),
+    /// A relative time such as `1h`
+    Duration(String),
+    /// A bare word such as an enum value (`LOAD`) or `true`, or an unquoted
+    /// dotted value such as `10.0.0.1`
+    Word(String),
+    /// A parenthesised list for `IN`
+    List(Vec<Value>),
+}
@@ -0,0 +1,245 @@
+//! Printing a syntax tree back out as canonically formatted FSQL
+//!
+//! Keywords and operators are upper case, each clause goes on its own line
+//! indented under the statement, and the conditions of a top level `AND` /
+//! `OR` chain get a line each with the keywords right aligned so that the
+//! conditions start in the same column:
+//!
+//! ```text
+//! QUERY module_activity.**
+//!   WITH module_activity.activity_id = LOAD
+//!    AND module_activity.actor.process.file.name = 'regsvr32.exe'
+//!   AFTER 1h
+//! ```
+//!
+//! Paths and values are written exactly as they were, as their case can
+//! matter. Conditions in parentheses stay on one line. A script of several
+//! statements is formatted one statement at a time.
+
+use super::ast::{Clause, Condition, Explain, Query, Statement, Value};
+use super::{ParseError, script};
+
+/// Indent of the clauses under a statement
+const INDENT: &str = "  ";
+
+/// Width the `WITH`, `AND` and `OR` keywords are aligned to
+const KEYWORD_WIDTH: usize = "WITH".len();
+
+/// Parse and format a script of one or more statements
+///
+/// Statements are separated by a blank line, and each keeps the `;` it was
+/// written with. Comments between statements are kept on lines of their
+/// own; a statement with comments inside it is left as it was written. The
+/// result never has a trailing newline.
+pub fn format(source: &str) -> Result<String, ParseError> {
+    let mut blocks = Vec::new();
+    for piece in script::split(source) {
+        let mut lines: Vec<String> = piece
+            .comments
+            .iter()
+            .map(|span| source[span.start..span.end].to_string())
+            .collect();
+        if piece.statement.is_some() {
+            let statement = piece.parse(source)?;
+            let mut text = if piece.has_comments() {
+                piece.text(source).to_string()
+            } else {
+                format_statement(&statement)
+            };
+            if piece.terminated {
+                text.push(';');
+            }
+            lines.push(text);
+        }
+        blocks.push(lines.join("\n"));
+    }
+    Ok(blocks.join("\n\n"))
+}
+
+/// Format a statement
+pub fn format_statement(statement: &Statement) -> String {
+    lines(statement).join("\n")
+}
+
+/// The lines of a formatted statement
+fn lines(statement: &Statement) -> Vec<String> {
+    match statement {
+        Statement::Query(query) => query_lines(if query.keyword { "QUERY " } else { "" }, query),
+        Statement::Validate(inner) => prefixed("VALIDATE", inner),
+        Statement::Summarize(summarize) => query_lines(
+            &format!("SUMMARIZE {} ", summarize.operation.to_uppercase()),
+            &summarize.query,
+        ),
+        Statement::Explain(Explain::Connectors) => vec!["EXPLAIN CONNECTORS".to_string()],
+        Statement::Explain(Explain::Version) => vec!["EXPLAIN VERSION".to_string()],
+        Statement::Explain(Explain::Schema(path)) => vec![format!("EXPLAIN SCHEMA {}", path.text)],
+        Statement::Explain(Explain::Attributes(inner)) => prefixed("EXPLAIN ATTRIBUTES", inner),
+        Statement::Explain(Explain::Graphql(inner)) => prefixed("EXPLAIN GRAPHQL", inner),
+        Statement::Explain(Explain::Statement(inner)) => prefixed("EXPLAIN", inner),
+    }
+}
+
+/// The lines of a statement wrapped by another, such as `VALIDATE`
+fn prefixed(prefix: &str, inner: &Statement) -> Vec<String> {
+    let mut lines = lines(inner);
+    lines[0] = format!("{} {}", prefix, lines[0]);
+    lines
+}
+
+/// The lines of a query, with `head` before its selections
+fn query_lines(head: &str, query: &Query) -> Vec<String> {
+    let selections = query
+        .selections
+        .iter()
+        .map(|path| path.text.as_str())
+        .collect::<Vec<_>>()
+        .join(", ");
+    let mut lines = vec![format!("{}{}", head, selections)];
+    for clause in &query.clauses {
+        match clause {
+            Clause::With(condition) => lines.extend(with_lines(condition)),
+            Clause::From(connectors) => lines.push(format!(
+                "{}FROM {}",
+                INDENT,
+                connectors.iter().map(value).collect::<Vec<_>>().join(", ")
+            )),
+            Clause::After(time) => lines.push(format!("{}AFTER {}", INDENT, value(time))),
+            Clause::Before(time) => lines.push(format!("{}BEFORE {}", INDENT, value(time))),
+        }
+    }
+    lines
+}
+
+/// The lines of a `WITH` clause, one per condition of a top level chain
+fn with_lines(condition: &Condition) -> Vec<String> {
+    let mut conditions: Vec<(&str, &Condition)> = Vec::new();
+    let branches = match condition {
+        Condition::Or(branches) => branches.as_slice(),
+        condition => std::slice::from_ref(condition),
+    };
+    for (i, branch) in branches.iter().enumerate() {
+        let keyword = if i == 0 { "WITH" } else { "OR" };
+        match branch {
+            Condition::And(items) => {
+                for (j, item) in items.iter().enumerate() {
+                    conditions.push((if j == 0 { keyword } else { "AND" }, item));
+                }
+            }
+            branch => conditions.push((keyword, branch)),
+        }
+    }
+    conditions
+        .into_iter()
+        .map(|(keyword, condition)| {
+            format!(
+                "{}{:>width$} {}",
+                INDENT,
+                keyword,
+                format_condition(condition),
+                width = KEYWORD_WIDTH
+            )
+        })
+        .collect()
+}
+
+/// Format a condition on a single line
+pub fn format_condition(condition: &Condition) -> String {
+    let join = |conditions: &[Condition], keyword: &str| {
+        conditions
+            .iter()
+            .map(format_condition)
+            .collect::<Vec<_>>()
+            .join(keyword)
+    };
+    match condition {
+        Condition::And(conditions) => join(conditions, " AND "),
+        Condition::Or(conditions) => join(conditions, " OR "),
+        Condition::Not(condition) => format!("NOT {}", format_condition(condition)),
+        Condition::Group(condition) => format!("({})", format_condition(condition)),
+        Condition::Comparison(comparison) => format!(
+            "{} {} {}",
+            comparison.path.text,
+            comparison.operator.as_str(),
+            value(&comparison.value)
+        ),
+    }
+}
+
+/// Format a value as it was written
+fn value(value: &Value) -> String {
+    match value {
+        Value::String(text) | Value::Number(text) | Value::Duration(text) | Value::Word(text) => {
+            text.clone()
+        }
+        Value::List(values) => format!(
+            "({})",
+            values
+                .iter()
+                .map(self::value)
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_query() {
+        let source = "query module_activity.**   with module_activity.activity_id = LOAD and\n module_activity.actor.process.file.name='regsvr32.exe' or (a.b in (1,2) and not a.c contains 'X') after 1h from splunk,'edr-1';";
+        let expected = "QUERY module_activity.**
+  WITH module_activity.activity_id = LOAD
+   AND module_activity.actor.process.file.name = 'regsvr32.exe'
+    OR (a.b IN (1, 2) AND NOT a.c CONTAINS 'X')
+  AFTER 1h
+  FROM splunk, 'edr-1';";
+        assert_eq!(format(source).unwrap(), expected);
+        // Formatting is stable
+        assert_eq!(format(expected).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_format_other_statements() {
+        assert_eq!(
+            format("validate a.* with a.b != 2 before '2025-01-01'").unwrap(),
+            "VALIDATE a.*\n  WITH a.b != 2\n  BEFORE '2025-01-01'"
+        );
+        assert_eq!(
+            format("explain graphql query a.*, b.x").unwrap(),
+            "EXPLAIN GRAPHQL QUERY a.*, b.x"
+        );
+        assert_eq!(
+            format("summarize count a.** after 7d").unwrap(),
+            "SUMMARIZE COUNT a.**\n  AFTER 7d"
+        );
+        assert_eq!(
+            format("explain   schema  module_activity;").unwrap(),
+            "EXPLAIN SCHEMA module_activity;"
+        );
+        assert!(format("QUERY a.** WITH").is_err());
+    }
+
+    #[test]
+    fn test_format_dotted_values() {
+        assert_eq!(
+            format(
+                "query a.* with a.ip=10.0.0.1 or a.name in (cmd.exe) after 2025-01-01T10:00:00.5Z"
+            )
+            .unwrap(),
+            "QUERY a.*\n  WITH a.ip = 10.0.0.1\n    OR a.name IN (cmd.exe)\n  AFTER 2025-01-01T10:00:00.5Z"
+        );
+    }
+
+    #[test]
+    fn test_format_script() {
+        let source = "-- Connectors\nexplain connectors;\n/* loads */\nquery a.* after 1h; query b.* /* keep */ with b.x=1\n-- end";
+        assert_eq!(
+            format(source).unwrap(),
+            "-- Connectors\nEXPLAIN CONNECTORS;\n\n/* loads */\nQUERY a.*\n  AFTER 1h;\n\nquery b.* /* keep */ with b.x=1\n\n-- end"
+        );
+        let error = format("QUERY a.*;\nQUERY b.* WITH;").unwrap_err();
+        assert_eq!((error.span.line, error.span.column), (2, 15));
+    }
+}
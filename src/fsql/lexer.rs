@@ -0,0 +1,281 @@
+//! Splitting FSQL input into tokens
+//!
+//! Tokenizing never fails: anything that can't be understood becomes an
+//! [`TokenKind::Unknown`] or [`TokenKind::Unterminated`] token, and it's left
+//! to the parser to report it. This lets partial input be tokenized while it
+//! is still being typed.
+
+use super::Span;
+
+/// What a token is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenKind {
+    /// A bare word: a keyword, an enum value or a single segment attribute
+    Word,
+    /// A dotted attribute path or wildcard, e.g. `process.file.*`
+    Path,
+    /// A quoted string literal, including its quotes
+    String,
+    /// A string literal with no closing quote
+    Unterminated,
+    Number,
+    /// A relative time such as `1h` or `30m`
+    Duration,
+    /// A comparison operator such as `=` or `!=`
+    Operator,
+    LParen,
+    RParen,
+    Comma,
+    Semicolon,
+    /// A REPL meta-command such as `\format`
+    Meta,
+    /// A `--` line comment or `/* */` block comment
+    Comment,
+    Unknown,
+}
+
+/// A token and where it was found
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub span: Span,
+}
+
+impl Token {
+    /// The text of the token
+    pub fn text<'a>(&self, source: &'a str) -> &'a str {
+        &source[self.span.start..self.span.end]
+    }
+
+    /// Whether this is a word matching a keyword, ignoring case
+    pub fn is_keyword(&self, source: &str, keyword: &str) -> bool {
+        self.kind == TokenKind::Word && self.text(source).eq_ignore_ascii_case(keyword)
+    }
+}
+
+/// Units that can follow a number to make a relative time
+pub const TIME_UNITS: &[&str] = &["s", "m", "h", "d", "w"];
+
+/// Characters that can be part of a word, path or number
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '_' | '.' | '*' | '-' | ':')
+}
+
+/// Characters that make up operators
+fn is_operator_char(c: char) -> bool {
+    matches!(c, '=' | '!' | '<' | '>' | '~')
+}
+
+/// Decide what kind of token a run of word characters is
+fn classify(word: &str) -> TokenKind {
+    if word.starts_with(|c: char| c.is_ascii_digit() || c == '-') && word.parse::<f64>().is_ok() {
+        TokenKind::Number
+    } else if TIME_UNITS.iter().any(|unit| {
+        word.strip_suffix(unit)
+            .is_some_and(|n| !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()))
+    }) {
+        TokenKind::Duration
+    } else if word.contains('.') || word.contains('*') {
+        TokenKind::Path
+    } else {
+        TokenKind::Word
+    }
+}
+
+/// Walks the source keeping track of line and column
+struct Cursor<'a> {
+    source: &'a str,
+    offset: usize,
+    line: usize,
+    column: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn peek(&self) -> Option<char> {
+        self.source[self.offset..].chars().next()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.offset += c.len_utf8();
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+        Some(c)
+    }
+
+    fn bump_while(&mut self, predicate: impl Fn(char) -> bool) {
+        while self.peek().is_some_and(&predicate) {
+            self.bump();
+        }
+    }
+}
+
+/// Split the source into tokens, skipping whitespace
+pub fn tokenize(source: &str) -> Vec<Token> {
+    let mut cursor = Cursor {
+        source,
+        offset: 0,
+        line: 1,
+        column: 1,
+    };
+    let mut tokens = Vec::new();
+
+    while let Some(c) = cursor.peek() {
+        if c.is_whitespace() {
+            cursor.bump();
+            continue;
+        }
+        let (start, line, column) = (cursor.offset, cursor.line, cursor.column);
+        cursor.bump();
+
+        let kind = match c {
+            '\'' | '"' => {
+                let mut escaped = false;
+                let mut closed = false;
+                while let Some(next) = cursor.bump() {
+                    if escaped {
+                        escaped = false;
+                    } else if next == '\\' {
+                        escaped = true;
+                    } else if next == c {
+                        closed = true;
+                        break;
+                    }
+                }
+                if closed {
+                    TokenKind::String
+                } else {
+                    TokenKind::Unterminated
+                }
+            }
+            '-' if cursor.peek() == Some('-') => {
+                cursor.bump_while(|next| next != '\n');
+                TokenKind::Comment
+            }
+            '/' if cursor.peek() == Some('*') => {
+                cursor.bump();
+                // An unclosed comment runs to the end of the input
+                while !cursor.source[cursor.offset..].starts_with("*/") && cursor.bump().is_some() {
+                }
+                cursor.bump();
+                cursor.bump();
+                TokenKind::Comment
+            }
+            '(' => TokenKind::LParen,
+            ')' => TokenKind::RParen,
+            ',' => TokenKind::Comma,
+            ';' => TokenKind::Semicolon,
+            '\\' if cursor.peek().is_some_and(char::is_alphabetic) => {
+                cursor.bump_while(char::is_alphanumeric);
+                TokenKind::Meta
+            }
+            c if is_operator_char(c) => {
+                cursor.bump_while(|next| matches!(next, '=' | '~'));
+                TokenKind::Operator
+            }
+            c if is_word_char(c) => {
+                cursor.bump_while(is_word_char);
+                classify(&source[start..cursor.offset])
+            }
+            _ => TokenKind::Unknown,
+        };
+
+        tokens.push(Token {
+            kind,
+            span: Span {
+                start,
+                end: cursor.offset,
+                line,
+                column,
+            },
+        });
+    }
+    tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(source: &str) -> Vec<(&str, TokenKind)> {
+        tokenize(source)
+            .into_iter()
+            .map(|token| (token.text(source), token.kind))
+            .collect()
+    }
+
+    #[test]
+    fn test_tokenize_query() {
+        assert_eq!(
+            kinds(
+                "QUERY module_activity.** WITH module_activity.actor.process.pid >= 42 AFTER 1h;"
+            ),
+            vec![
+                ("QUERY", TokenKind::Word),
+                ("module_activity.**", TokenKind::Path),
+                ("WITH", TokenKind::Word),
+                ("module_activity.actor.process.pid", TokenKind::Path),
+                (">=", TokenKind::Operator),
+                ("42", TokenKind::Number),
+                ("AFTER", TokenKind::Word),
+                ("1h", TokenKind::Duration),
+                (";", TokenKind::Semicolon),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tokenize_literals() {
+        assert_eq!(
+            kinds(r#"x IN ('a\'b', "c", -1.5) \format 'open"#),
+            vec![
+                ("x", TokenKind::Word),
+                ("IN", TokenKind::Word),
+                ("(", TokenKind::LParen),
+                (r"'a\'b'", TokenKind::String),
+                (",", TokenKind::Comma),
+                (r#""c""#, TokenKind::String),
+                (",", TokenKind::Comma),
+                ("-1.5", TokenKind::Number),
+                (")", TokenKind::RParen),
+                ("\\format", TokenKind::Meta),
+                ("'open", TokenKind::Unterminated),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tokenize_comments() {
+        assert_eq!(
+            kinds("-- find loads\nQUERY a.* /* all; of it */ WITH a.b = '--x' -- done"),
+            vec![
+                ("-- find loads", TokenKind::Comment),
+                ("QUERY", TokenKind::Word),
+                ("a.*", TokenKind::Path),
+                ("/* all; of it */", TokenKind::Comment),
+                ("WITH", TokenKind::Word),
+                ("a.b", TokenKind::Path),
+                ("=", TokenKind::Operator),
+                ("'--x'", TokenKind::String),
+                ("-- done", TokenKind::Comment),
+            ]
+        );
+        assert_eq!(kinds("/* open"), vec![("/* open", TokenKind::Comment)]);
+        assert_eq!(kinds("-1"), vec![("-1", TokenKind::Number)]);
+    }
+
+    #[test]
+    fn test_token_positions() {
+        let tokens = tokenize("QUERY\n  a.b = 'é' ?");
+        let positions: Vec<(usize, usize)> = tokens
+            .iter()
+            .map(|token| (token.span.line, token.span.column))
+            .collect();
+        assert_eq!(positions, vec![(1, 1), (2, 3), (2, 7), (2, 9), (2, 13)]);
+        assert_eq!(tokens[4].kind, TokenKind::Unknown);
+    }
+}
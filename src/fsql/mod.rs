@@ -0,0 +1,143 @@
+//! Offline tokenizing and parsing of FSQL
+//!
+//! The [`lexer`] splits input into tokens and never fails, so it can be used
+//! on partial REPL input for highlighting and completion. The [`parser`]
+//! builds an [`ast::Statement`] from a complete command, reporting the first
+//! syntax error with the line and column where it was found. Neither needs the
+//! API, so syntax can be checked without a round trip through `VALIDATE`.
+//! [`format`] prints a statement back out in a canonical layout, and
+//! [`script`] splits a script of several statements apart.
+
+pub mod ast;
+pub mod format;
+pub mod lexer;
+pub mod parser;
+pub mod script;
+
+pub use parser::parse;
+
+use lexer::TokenKind;
+
+/// Remove `--` and `/* */` comments, leaving string literals alone
+///
+/// A block comment is replaced by a space so that the words either side of
+/// it stay apart.
+pub fn strip_comments(source: &str) -> String {
+    let mut text = String::with_capacity(source.len());
+    let mut last = 0;
+    for token in lexer::tokenize(source)
+        .iter()
+        .filter(|token| token.kind == TokenKind::Comment)
+    {
+        text.push_str(&source[last..token.span.start]);
+        if token.text(source).starts_with("/*") {
+            text.push(' ');
+        }
+        last = token.span.end;
+    }
+    text.push_str(&source[last..]);
+    text
+}
+
+/// A range of the source text
+///
+/// `start` and `end` are byte offsets; `line` and `column` (both counted
+/// from 1, with columns in characters) locate `start` for people.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Span {
+    /// A span covering both this span and another that follows it
+    pub fn to(self, other: Span) -> Span {
+        Span {
+            end: other.end,
+            ..self
+        }
+    }
+}
+
+/// A syntax error found while parsing
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub message: String,
+    pub span: Span,
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "line {}, column {}: {}",
+            self.span.line, self.span.column, self.message
+        )
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl ParseError {
+    /// Show the error under the line of source it refers to
+    ///
+    /// ```text
+    /// line 1, column 23: expected a value after '='
+    ///   |
+    /// 1 | QUERY a.** WITH a.b = AND x.y = 1
+    ///   |                       ^^^
+    /// ```
+    pub fn render(&self, source: &str) -> String {
+        let line_text = source.lines().nth(self.span.line - 1).unwrap_or("");
+        let number = self.span.line.to_string();
+        let gutter = " ".repeat(number.len());
+        let line_start = source[..self.span.start.min(source.len())]
+            .rfind('\n')
+            .map_or(0, |i| i + 1);
+        let width = source
+            .get(self.span.start..self.span.end)
+            .map(|text| text.lines().next().unwrap_or("").chars().count())
+            .unwrap_or(0)
+            .max(1);
+        let indent = source[line_start..self.span.start.min(source.len())]
+            .chars()
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect::<String>();
+        format!(
+            "{}\n{} |\n{} | {}\n{} | {}{}",
+            self,
+            gutter,
+            number,
+            line_text,
+            gutter,
+            indent,
+            "^".repeat(width)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_comments() {
+        assert_eq!(
+            strip_comments("-- loads\nQUERY a.*/* all */WITH a.b = '--not /* a comment */' -- end"),
+            "\nQUERY a.* WITH a.b = '--not /* a comment */' "
+        );
+        assert_eq!(strip_comments("QUERY a.* /* open"), "QUERY a.*  ");
+    }
+
+    #[test]
+    fn test_render_error() {
+        let source = "QUERY a.**\nWITH a.b = AND x.y = 1";
+        let error = parse(source).unwrap_err();
+        assert_eq!(
+            error.render(source),
+            "line 2, column 12: expected a value after '=', found 'AND'\n  |\n2 | WITH a.b = AND x.y = 1\n  |            ^^^"
+        );
+    }
+}
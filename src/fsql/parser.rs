@@ -0,0 +1,634 @@
+//! Parsing FSQL statements into a syntax tree
+//!
+//! This is a recursive descent parser over the tokens from
+//! [`super::lexer`]. Keywords are matched without regard to case. Parsing
+//! stops at the first error, which carries the span of the offending token
+//! (or the end of the input when something is missing).
+
+use super::ast::{Clause, Comparison, Condition, Explain, Operator, Path, Query, Statement, Value};
+use super::lexer::{self, Token, TokenKind};
+use super::{ParseError, Span};
+
+/// Keywords that can't be used as attribute names or values
+const RESERVED: &[&str] = &[
+    "QUERY",
+    "EXPLAIN",
+    "VALIDATE",
+    "SUMMARIZE",
+    "WITH",
+    "FROM",
+    "AFTER",
+    "BEFORE",
+    "AND",
+    "OR",
+    "NOT",
+];
+
+/// Verbs that start a statement
+const VERBS: &[&str] = &["QUERY", "EXPLAIN", "VALIDATE", "SUMMARIZE"];
+
+/// Parse a single FSQL statement, optionally ending with `;`
+pub fn parse(source: &str) -> Result<Statement, ParseError> {
+    parse_tokens(source, lexer::tokenize(source))
+}
+
+/// Parse a statement from some of the tokens of the source
+///
+/// Comments are skipped. Spans are relative to the whole source.
+pub(super) fn parse_tokens(source: &str, tokens: Vec<Token>) -> Result<Statement, ParseError> {
+    let mut parser = Parser::new(source, tokens);
+    parser.check_tokens()?;
+    let statement = parser.statement()?;
+    parser.eat(TokenKind::Semicolon);
+    match parser.peek() {
+        None => Ok(statement),
+        Some(token) => Err(parser.error(
+            Some(token),
+            format!(
+                "expected the end of the statement, found {}",
+                parser.describe(Some(token))
+            ),
+        )),
+    }
+}
+
+struct Parser<'a> {
+    source: &'a str,
+    tokens: Vec<Token>,
+    position: usize,
+    /// Where something missing at the end is reported
+    end: Span,
+}
+
+impl<'a> Parser<'a> {
+    fn new(source: &'a str, tokens: Vec<Token>) -> Self {
+        let tokens: Vec<Token> = tokens
+            .into_iter()
+            .filter(|token| token.kind != TokenKind::Comment)
+            .collect();
+        let end = match tokens.last() {
+            Some(last) => end_span(&source[..last.span.end]),
+            None => end_span(source),
+        };
+        Self {
+            source,
+            tokens,
+            position: 0,
+            end,
+        }
+    }
+
+    /// Report tokens that can never be valid before parsing starts
+    fn check_tokens(&self) -> Result<(), ParseError> {
+        for token in &self.tokens {
+            let message = match token.kind {
+                TokenKind::Unterminated => "unterminated string literal".to_string(),
+                TokenKind::Unknown | TokenKind::Meta => {
+                    format!("unexpected character {}", self.describe(Some(*token)))
+                }
+                _ => continue,
+            };
+            return Err(self.error(Some(*token), message));
+        }
+        Ok(())
+    }
+
+    fn peek(&self) -> Option<Token> {
+        self.tokens.get(self.position).copied()
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.peek();
+        self.position += usize::from(token.is_some());
+        token
+    }
+
+    fn text(&self, token: Token) -> &'a str {
+        token.text(self.source)
+    }
+
+    /// The span of the most recently consumed token
+    fn last_span(&self) -> Span {
+        self.position
+            .checked_sub(1)
+            .map(|i| self.tokens[i].span)
+            .unwrap_or_default()
+    }
+
+    fn at_keyword(&self, keyword: &str) -> bool {
+        self.peek()
+            .is_some_and(|token| token.is_keyword(self.source, keyword))
+    }
+
+    fn eat_keyword(&mut self, keyword: &str) -> Option<Token> {
+        self.at_keyword(keyword).then(|| self.next()).flatten()
+    }
+
+    fn eat(&mut self, kind: TokenKind) -> Option<Token> {
+        self.peek()
+            .filter(|token| token.kind == kind)
+            .and_then(|_| self.next())
+    }
+
+    fn is_reserved(&self, token: Token) -> bool {
+        RESERVED
+            .iter()
+            .any(|keyword| token.is_keyword(self.source, keyword))
+    }
+
+    /// Describe a token for an error message
+    fn describe(&self, token: Option<Token>) -> String {
+        match token {
+            Some(token) => format!("'{}'", self.text(token)),
+            None => "the end of the input".to_string(),
+        }
+    }
+
+    /// Build an error at a token, or at the end of the input
+    fn error(&self, token: Option<Token>, message: impl Into<String>) -> ParseError {
+        let span = match token {
+            Some(token) => token.span,
+            None => self.end,
+        };
+        ParseError {
+            message: message.into(),
+            span,
+        }
+    }
+
+    /// Build an "expected X, found Y" error at the next token
+    fn expected(&self, what: &str) -> ParseError {
+        let token = self.peek();
+        self.error(
+            token,
+            format!("expected {}, found {}", what, self.describe(token)),
+        )
+    }
+
+    fn statement(&mut self) -> Result<Statement, ParseError> {
+        let Some(verb) = self.next() else {
+            return Err(self.expected("a command (QUERY, EXPLAIN, VALIDATE or SUMMARIZE)"));
+        };
+        match self.text(verb).to_uppercase().as_str() {
+            "QUERY" if verb.kind == TokenKind::Word => {
+                Ok(Statement::Query(self.query(true, verb.span)?))
+            }
+            "VALIDATE" if verb.kind == TokenKind::Word => {
+                Ok(Statement::Validate(Box::new(self.inner("VALIDATE")?)))
+            }
+            "SUMMARIZE" if verb.kind == TokenKind::Word => self.summarize(),
+            "EXPLAIN" if verb.kind == TokenKind::Word => self.explain(),
+            _ => Err(self.error(
+                Some(verb),
+                format!(
+                    "expected a command (QUERY, EXPLAIN, VALIDATE or SUMMARIZE), found {}",
+                    self.describe(Some(verb))
+                ),
+            )),
+        }
+    }
+
+    /// Parse the statement wrapped by `VALIDATE` or `EXPLAIN`
+    ///
+    /// A query can be given without its `QUERY` keyword.
+    fn inner(&mut self, after: &str) -> Result<Statement, ParseError> {
+        match self.peek() {
+            None => Err(self.expected(&format!("FSQL after {}", after))),
+            Some(_) if VERBS.iter().any(|verb| self.at_keyword(verb)) => self.statement(),
+            Some(token) => Ok(Statement::Query(self.query(false, token.span)?)),
+        }
+    }
+
+    fn summarize(&mut self) -> Result<Statement, ParseError> {
+        let operation = match self.peek() {
+            Some(token) if token.kind == TokenKind::Word && !self.is_reserved(token) => {
+                self.next();
+                self.text(token).to_string()
+            }
+            _ => return Err(self.expected("an operation such as COUNT after SUMMARIZE")),
+        };
+        let start = self.peek().map(|token| token.span).unwrap_or_default();
+        Ok(Statement::Summarize(super::ast::Summarize {
+            operation,
+            query: self.query(false, start)?,
+        }))
+    }
+
+    fn explain(&mut self) -> Result<Statement, ParseError> {
+        let explain = if self.eat_keyword("CONNECTORS").is_some() {
+            Explain::Connectors
+        } else if self.eat_keyword("VERSION").is_some() {
+            Explain::Version
+        } else if self.eat_keyword("SCHEMA").is_some() {
+            Explain::Schema(self.path("an attribute path after SCHEMA")?)
+        } else if self.eat_keyword("ATTRIBUTES").is_some() {
+            Explain::Attributes(Box::new(self.inner("ATTRIBUTES")?))
+        } else if self.eat_keyword("GRAPHQL").is_some() {
+            Explain::Graphql(Box::new(self.inner("GRAPHQL")?))
+        } else {
+            Explain::Statement(Box::new(self.inner("EXPLAIN")?))
+        };
+        Ok(Statement::Explain(explain))
+    }
+
+    /// Parse selections and clauses, starting at `start`
+    fn query(&mut self, keyword: bool, start: Span) -> Result<Query, ParseError> {
+        let mut selections = vec![self.path("an attribute path to select")?];
+        while self.eat(TokenKind::Comma).is_some() {
+            selections.push(self.path("an attribute path after ','")?);
+        }
+
+        let mut clauses = Vec::new();
+        loop {
+            if self.eat_keyword("WITH").is_some() {
+                clauses.push(Clause::With(self.or()?));
+            } else if self.eat_keyword("FROM").is_some() {
+                let mut connectors = vec![self.connector()?];
+                while self.eat(TokenKind::Comma).is_some() {
+                    connectors.push(self.connector()?);
+                }
+                clauses.push(Clause::From(connectors));
+            } else if self.eat_keyword("AFTER").is_some() {
+                clauses.push(Clause::After(self.time("AFTER")?));
+            } else if self.eat_keyword("BEFORE").is_some() {
+                clauses.push(Clause::Before(self.time("BEFORE")?));
+            } else {
+                break;
+            }
+        }
+
+        if self
+            .peek()
+            .is_some_and(|token| token.kind != TokenKind::Semicolon)
+        {
+            return Err(self.expected("WITH, FROM, AFTER, BEFORE or the end of the query"));
+        }
+        Ok(Query {
+            keyword,
+            selections,
+            clauses,
+            span: start.to(self.last_span()),
+        })
+    }
+
+    fn path(&mut self, what: &str) -> Result<Path, ParseError> {
+        match self.peek() {
+            Some(token)
+                if matches!(token.kind, TokenKind::Path | TokenKind::Word)
+                    && !self.is_reserved(token) =>
+            {
+                self.next();
+                Ok(Path {
+                    text: self.text(token).to_string(),
+                    span: token.span,
+                })
+            }
+            _ => Err(self.expected(what)),
+        }
+    }
+
+    fn connector(&mut self) -> Result<Value, ParseError> {
+        match self.peek() {
+            Some(token) if token.kind == TokenKind::String => {
+                self.next();
+                Ok(Value::String(self.text(token).to_string()))
+            }
+            Some(token)
+                if matches!(token.kind, TokenKind::Word | TokenKind::Path)
+                    && !self.is_reserved(token) =>
+            {
+                self.next();
+                Ok(Value::Word(self.text(token).to_string()))
+            }
+            _ => Err(self.expected("a connector name after FROM")),
+        }
+    }
+
+    fn time(&mut self, clause: &str) -> Result<Value, ParseError> {
+        match self.peek() {
+            Some(token) if !self.is_reserved(token) => {
+                let text = self.text(token).to_string();
+                let value = match token.kind {
+                    TokenKind::Duration => Value::Duration(text),
+                    TokenKind::String => Value::String(text),
+                    TokenKind::Number => Value::Number(text),
+                    // Unquoted timestamps such as 2025-01-01T10:00:00.5Z
+                    TokenKind::Word | TokenKind::Path => Value::Word(text),
+                    _ => return Err(self.expected(&format!("a time such as 1h after {}", clause))),
+                };
+                self.next();
+                Ok(value)
+            }
+            _ => Err(self.expected(&format!("a time such as 1h after {}", clause))),
+        }
+    }
+
+    fn or(&mut self) -> Result<Condition, ParseError> {
+        let mut conditions = vec![self.and()?];
+        while self.eat_keyword("OR").is_some() {
+            conditions.push(self.and()?);
+        }
+        Ok(if conditions.len() == 1 {
+            conditions.remove(0)
+        } else {
+            Condition::Or(conditions)
+        })
+    }
+
+    fn and(&mut self) -> Result<Condition, ParseError> {
+        let mut conditions = vec![self.unary()?];
+        while self.eat_keyword("AND").is_some() {
+            conditions.push(self.unary()?);
+        }
+        Ok(if conditions.len() == 1 {
+            conditions.remove(0)
+        } else {
+            Condition::And(conditions)
+        })
+    }
+
+    fn unary(&mut self) -> Result<Condition, ParseError> {
+        if self.eat_keyword("NOT").is_some() {
+            return Ok(Condition::Not(Box::new(self.unary()?)));
+        }
+        if let Some(open) = self.eat(TokenKind::LParen) {
+            let condition = self.or()?;
+            if self.eat(TokenKind::RParen).is_none() {
+                return Err(self.expected(&format!(
+                    "')' to close the '(' at line {}, column {}",
+                    open.span.line, open.span.column
+                )));
+            }
+            return Ok(Condition::Group(Box::new(condition)));
+        }
+        self.comparison().map(Condition::Comparison)
+    }
+
+    fn comparison(&mut self) -> Result<Comparison, ParseError> {
+        let path = self.path("an attribute path in the condition")?;
+        let operator = self
+            .peek()
+            .filter(|token| matches!(token.kind, TokenKind::Operator | TokenKind::Word))
+            .and_then(|token| Operator::parse(self.text(token)));
+        let Some(operator) = operator else {
+            return Err(self.expected(&format!("a comparison operator after '{}'", path.text)));
+        };
+        self.next();
+
+        let value = if operator == Operator::In {
+            self.list()?
+        } else {
+            self.value(&format!("a value after '{}'", operator.as_str()))?
+        };
+        Ok(Comparison {
+            span: path.span.to(self.last_span()),
+            path,
+            operator,
+            value,
+        })
+    }
+
+    /// Parse the parenthesised list of values for `IN`
+    fn list(&mut self) -> Result<Value, ParseError> {
+        if self.eat(TokenKind::LParen).is_none() {
+            return Err(self.expected("'(' after IN"));
+        }
+        let mut values = vec![self.value("a value in the list")?];
+        while self.eat(TokenKind::Comma).is_some() {
+            values.push(self.value("a value after ','")?);
+        }
+        if self.eat(TokenKind::RParen).is_none() {
+            return Err(self.expected("',' or ')' in the list"));
+        }
+        Ok(Value::List(values))
+    }
+
+    fn value(&mut self, what: &str) -> Result<Value, ParseError> {
+        let Some(token) = self.peek().filter(|token| !self.is_reserved(*token)) else {
+            return Err(self.expected(what));
+        };
+        let text = self.text(token).to_string();
+        let value = match token.kind {
+            TokenKind::String => Value::String(text),
+            TokenKind::Number => Value::Number(text),
+            TokenKind::Duration => Value::Duration(text),
+            // Dotted values such as 10.0.0.1 or cmd.exe lex as paths
+            TokenKind::Word | TokenKind::Path => Value::Word(text),
+            _ => return Err(self.expected(what)),
+        };
+        self.next();
+        Ok(value)
+    }
+}
+
+/// A zero width span just after the last thing in the source
+fn end_span(source: &str) -> Span {
+    let source = source.trim_end();
+    let line = source.matches('\n').count() + 1;
+    let column = source
+        .rsplit('\n')
+        .next()
+        .map_or(0, |last| last.chars().count())
+        + 1;
+    Span {
+        start: source.len(),
+        end: source.len(),
+        line,
+        column,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn query(statement: Statement) -> Query {
+        match statement {
+            Statement::Query(query) => query,
+            other => panic!("not a query: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_query() {
+        let source = "QUERY module_activity.** WITH module_activity.activity_id = LOAD AND module_activity.actor.process.file.name = 'regsvr32.exe' AFTER 1h;";
+        let query = query(parse(source).unwrap());
+        assert!(query.keyword);
+        assert_eq!(query.selections[0].text, "module_activity.**");
+        assert_eq!(query.span.start, 0);
+        assert_eq!(query.span.end, source.len() - 1);
+
+        let Clause::With(Condition::And(conditions)) = &query.clauses[0] else {
+            panic!("expected an AND chain: {:?}", query.clauses);
+        };
+        assert_eq!(conditions.len(), 2);
+        let Condition::Comparison(comparison) = &conditions[1] else {
+            panic!("expected a comparison: {:?}", conditions[1]);
+        };
+        assert_eq!(comparison.operator, Operator::Eq);
+        assert_eq!(
+            comparison.value,
+            Value::String("'regsvr32.exe'".to_string())
+        );
+        assert_eq!(
+            &source[comparison.span.start..comparison.span.end],
+            "module_activity.actor.process.file.name = 'regsvr32.exe'"
+        );
+        assert_eq!(
+            query.clauses[1],
+            Clause::After(Value::Duration("1h".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_parse_precedence_and_groups() {
+        let query = query(
+            parse("query a.* with not a.x = 1 or (a.y in (1, 2) and a.z contains 'q') from splunk, 'edr-1' before '2025-01-01'")
+                .unwrap(),
+        );
+        let Clause::With(Condition::Or(branches)) = &query.clauses[0] else {
+            panic!("expected an OR chain: {:?}", query.clauses);
+        };
+        assert!(matches!(branches[0], Condition::Not(_)));
+        let Condition::Group(group) = &branches[1] else {
+            panic!("expected a group: {:?}", branches[1]);
+        };
+        assert!(matches!(**group, Condition::And(_)));
+        assert_eq!(
+            query.clauses[1],
+            Clause::From(vec![
+                Value::Word("splunk".to_string()),
+                Value::String("'edr-1'".to_string())
+            ])
+        );
+        assert_eq!(
+            query.clauses[2],
+            Clause::Before(Value::String("'2025-01-01'".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_parse_other_statements() {
+        assert_eq!(
+            parse("EXPLAIN CONNECTORS;").unwrap(),
+            Statement::Explain(Explain::Connectors)
+        );
+        assert!(matches!(
+            parse("explain schema module_activity").unwrap(),
+            Statement::Explain(Explain::Schema(path)) if path.text == "module_activity"
+        ));
+        assert!(matches!(
+            parse("EXPLAIN GRAPHQL QUERY module_activity.**").unwrap(),
+            Statement::Explain(Explain::Graphql(inner)) if matches!(*inner, Statement::Query(ref q) if q.keyword)
+        ));
+        assert!(matches!(
+            parse("VALIDATE module_activity.** AFTER 1d").unwrap(),
+            Statement::Validate(inner) if matches!(*inner, Statement::Query(ref q) if !q.keyword)
+        ));
+        assert!(matches!(
+            parse("SUMMARIZE COUNT module_activity.uid WITH module_activity.severity_id >= 3").unwrap(),
+            Statement::Summarize(summarize) if summarize.operation == "COUNT" && summarize.query.clauses.len() == 1
+        ));
+    }
+
+    #[test]
+    fn test_parse_dotted_values() {
+        let query = query(
+            parse("QUERY a.* WITH a.ip = 10.0.0.1 AND a.name IN (cmd.exe, 'x') AFTER 2025-01-01T10:00:00.5Z")
+                .unwrap(),
+        );
+        let Clause::With(Condition::And(conditions)) = &query.clauses[0] else {
+            panic!("expected an AND chain: {:?}", query.clauses);
+        };
+        let Condition::Comparison(comparison) = &conditions[0] else {
+            panic!("expected a comparison: {:?}", conditions[0]);
+        };
+        assert_eq!(comparison.value, Value::Word("10.0.0.1".to_string()));
+        let Condition::Comparison(comparison) = &conditions[1] else {
+            panic!("expected a comparison: {:?}", conditions[1]);
+        };
+        assert_eq!(
+            comparison.value,
+            Value::List(vec![
+                Value::Word("cmd.exe".to_string()),
+                Value::String("'x'".to_string())
+            ])
+        );
+        assert_eq!(
+            query.clauses[1],
+            Clause::After(Value::Word("2025-01-01T10:00:00.5Z".to_string()))
+        );
+    }
+
+    fn error(source: &str) -> (usize, usize, String) {
+        let error = parse(source).unwrap_err();
+        (error.span.line, error.span.column, error.message)
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(
+            error("SELECT * FROM x"),
+            (
+                1,
+                1,
+                "expected a command (QUERY, EXPLAIN, VALIDATE or SUMMARIZE), found 'SELECT'"
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            error("QUERY"),
+            (
+                1,
+                6,
+                "expected an attribute path to select, found the end of the input".to_string()
+            )
+        );
+        assert_eq!(
+            error("QUERY a.*\nWITH a.b ="),
+            (
+                2,
+                11,
+                "expected a value after '=', found the end of the input".to_string()
+            )
+        );
+        assert_eq!(
+            error("QUERY a.* WITH (a.b = 1"),
+            (
+                1,
+                24,
+                "expected ')' to close the '(' at line 1, column 16, found the end of the input"
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            error("QUERY a.* WITH a.b = 'x"),
+            (1, 22, "unterminated string literal".to_string())
+        );
+        assert_eq!(
+            error("QUERY a.* LIMIT 10"),
+            (
+                1,
+                11,
+                "expected WITH, FROM, AFTER, BEFORE or the end of the query, found 'LIMIT'"
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            error("QUERY a.* WITH a.b LIKE 1"),
+            (
+                1,
+                20,
+                "expected a comparison operator after 'a.b', found 'LIKE'".to_string()
+            )
+        );
+        assert_eq!(
+            error("EXPLAIN VERSION now"),
+            (
+                1,
+                17,
+                "expected the end of the statement, found 'now'".to_string()
+            )
+        );
+    }
+}
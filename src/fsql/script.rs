@@ -0,0 +1,128 @@
+//! Splitting a script into the statements it contains
+//!
+//! Statements are separated by `;`. Because the split is made on tokens, a
+//! `;` in a string literal or comment doesn't end a statement.
+
+use super::ast::Statement;
+use super::lexer::{self, Token, TokenKind};
+use super::parser;
+use super::{ParseError, Span};
+
+/// A statement of a script, with the comments that come before it
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Piece {
+    /// Comments between the previous statement and this one
+    pub comments: Vec<Span>,
+    /// The statement, without the `;` that ends it
+    ///
+    /// Only `None` for comments after the last statement.
+    pub statement: Option<Span>,
+    /// Whether the statement ends with `;`
+    pub terminated: bool,
+    /// The tokens of the statement, including its `;`
+    tokens: Vec<Token>,
+}
+
+impl Piece {
+    /// The text of the statement
+    pub fn text<'a>(&self, source: &'a str) -> &'a str {
+        self.statement
+            .map_or("", |span| &source[span.start..span.end])
+    }
+
+    /// Parse the statement, with spans relative to the whole script
+    pub fn parse(&self, source: &str) -> Result<Statement, ParseError> {
+        parser::parse_tokens(source, self.tokens.clone())
+    }
+
+    /// Whether there are comments within the statement itself
+    pub fn has_comments(&self) -> bool {
+        self.tokens
+            .iter()
+            .any(|token| token.kind == TokenKind::Comment)
+    }
+}
+
+/// Split a script into statements
+///
+/// Empty statements (such as `;;`) are dropped. Comments after the last
+/// statement end up in a piece of their own, with no statement.
+pub fn split(source: &str) -> Vec<Piece> {
+    let mut pieces = Vec::new();
+    let mut piece = Piece::default();
+    for token in lexer::tokenize(source) {
+        match token.kind {
+            TokenKind::Comment if piece.tokens.is_empty() => piece.comments.push(token.span),
+            TokenKind::Semicolon => {
+                if !piece.tokens.is_empty() {
+                    piece.terminated = true;
+                    piece.tokens.push(token);
+                    pieces.push(std::mem::take(&mut piece));
+                }
+            }
+            _ => {
+                piece.statement = Some(match piece.statement {
+                    Some(span) if token.kind == TokenKind::Comment => span,
+                    Some(span) => span.to(token.span),
+                    None => token.span,
+                });
+                piece.tokens.push(token);
+            }
+        }
+    }
+
+    // Comments after an unterminated last statement are kept apart from it
+    let mut trailing = Vec::new();
+    while piece
+        .tokens
+        .last()
+        .is_some_and(|token| token.kind == TokenKind::Comment)
+    {
+        trailing.extend(piece.tokens.pop().map(|token| token.span));
+    }
+    trailing.reverse();
+    if !piece.tokens.is_empty() || !piece.comments.is_empty() {
+        pieces.push(piece);
+    }
+    if !trailing.is_empty() {
+        pieces.push(Piece {
+            comments: trailing,
+            ..Piece::default()
+        });
+    }
+    pieces
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_script() {
+        let source = "-- first\nQUERY a.* WITH a.b = ';' ;\n\n/* second; */ EXPLAIN CONNECTORS;;\nQUERY b.* -- inline\n  AFTER 1h\n-- the end";
+        let pieces = split(source);
+        let texts: Vec<&str> = pieces[..3].iter().map(|piece| piece.text(source)).collect();
+        assert_eq!(
+            texts,
+            vec![
+                "QUERY a.* WITH a.b = ';'",
+                "EXPLAIN CONNECTORS",
+                "QUERY b.* -- inline\n  AFTER 1h",
+            ]
+        );
+        assert_eq!(pieces[0].comments.len(), 1);
+        assert!(pieces[0].terminated);
+        assert!(!pieces[2].terminated);
+        assert!(pieces[2].has_comments());
+        // The comment at the end is kept apart from the last statement
+        assert_eq!(pieces[3].statement, None);
+        assert_eq!(pieces[3].comments.len(), 1);
+
+        let error = split("QUERY a.*;\nQUERY b.* WITH;")[1]
+            .parse("QUERY a.*;\nQUERY b.* WITH;")
+            .unwrap_err();
+        assert_eq!((error.span.line, error.span.column), (2, 15));
+        assert_eq!(split("-- only a comment")[0].statement, None);
+        assert!(split(" ;; ").is_empty());
+    }
+}
@@ -0,0 +1,87 @@
+//! The rustyline helper used by the REPL
+//!
+//! [`FsqlHelper`] ties the REPL's line editing features together. Completion
+//! logic lives in [`crate::completion`] and syntax highlighting in
+//! [`crate::highlight`].
+
+use crate::api::Connection;
+use crate::completion;
+use crate::highlight;
+use crate::schema::SchemaCache;
+use rustyline::completion::{Completer, FilenameCompleter, Pair};
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::validate::Validator;
+use rustyline::{Context, Helper};
+use std::borrow::Cow;
+use std::cell::RefCell;
+
+/// Line editing support for FSQL input
+pub struct FsqlHelper {
+    /// Completes paths given to `\o`
+    filenames: FilenameCompleter,
+    /// Classes and attributes, fetched from the API as they're completed
+    schema: RefCell<SchemaCache>,
+}
+
+impl FsqlHelper {
+    pub fn new(connection: &Connection) -> Self {
+        Self {
+            filenames: FilenameCompleter::new(),
+            schema: RefCell::new(SchemaCache::new(Some(connection.clone()))),
+        }
+    }
+}
+
+impl Completer for FsqlHelper {
+    type Candidate = Pair;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<Pair>)> {
+        let first_word = line.split_whitespace().next().unwrap_or("");
+        if first_word.eq_ignore_ascii_case("\\o") && pos > first_word.len() {
+            return self.filenames.complete(line, pos, ctx);
+        }
+
+        let (start, candidates) =
+            completion::complete(line, pos, Some(&mut self.schema.borrow_mut()));
+        let pairs = candidates
+            .into_iter()
+            .map(|candidate| Pair {
+                // Only show the last part of attribute paths in the list
+                display: candidate
+                    .rsplit_once('.')
+                    .map_or(candidate.as_str(), |(_, last)| last)
+                    .to_string(),
+                replacement: candidate,
+            })
+            .collect();
+        Ok((start, pairs))
+    }
+}
+
+impl Hinter for FsqlHelper {
+    type Hint = String;
+}
+
+impl Highlighter for FsqlHelper {
+    fn highlight<'l>(&self, line: &'l str, pos: usize) -> Cow<'l, str> {
+        match highlight::highlight(line, pos) {
+            Some(highlighted) => Cow::Owned(highlighted),
+            None => Cow::Borrowed(line),
+        }
+    }
+
+    fn highlight_char(&self, _line: &str, _pos: usize, _forced: bool) -> bool {
+        // Every keystroke or cursor move can change the colors or emphasis
+        true
+    }
+}
+
+impl Validator for FsqlHelper {}
+
+impl Helper for FsqlHelper {}
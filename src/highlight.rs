@@ -0,0 +1,222 @@
+//! Syntax highlighting of REPL input
+//!
+//! Each line is split with the FSQL [`lexer`] and colored by kind as the user
+//! types: keywords, operators, string literals, numbers, time ranges such as
+//! `1h`, attribute paths and comments. When the cursor is next to a bracket or
+//! quote, it and its partner are emphasised. Colors come from `colored`, so
+//! they're turned off by `NO_COLOR`.
+
+use crate::completion::{CLAUSES, SUB_VERBS, VERBS};
+use crate::fsql::ast::Operator;
+use crate::fsql::lexer::{self, Token, TokenKind};
+use colored::Colorize;
+
+/// Keywords that aren't verbs or clauses
+const OTHER_KEYWORDS: &[&str] = &["NOT"];
+
+/// What a span of input is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Keyword,
+    Operator,
+    String,
+    /// A string literal with no closing quote
+    Unterminated,
+    Number,
+    TimeRange,
+    Path,
+    Bracket,
+    Meta,
+    Comment,
+    Plain,
+}
+
+/// A classified range of bytes in a line
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    pub kind: Kind,
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Decide how to color a token
+fn classify(line: &str, token: &Token) -> Kind {
+    match token.kind {
+        TokenKind::Word => {
+            let upper = token.text(line).to_uppercase();
+            let keyword = [VERBS, SUB_VERBS, CLAUSES, OTHER_KEYWORDS]
+                .iter()
+                .any(|words| words.contains(&upper.as_str()));
+            if keyword || Operator::parse(&upper).is_some() {
+                Kind::Keyword
+            } else {
+                Kind::Plain
+            }
+        }
+        TokenKind::Path => Kind::Path,
+        TokenKind::String => Kind::String,
+        TokenKind::Unterminated => Kind::Unterminated,
+        TokenKind::Number => Kind::Number,
+        TokenKind::Duration => Kind::TimeRange,
+        TokenKind::Operator | TokenKind::Comma | TokenKind::Semicolon => Kind::Operator,
+        TokenKind::LParen | TokenKind::RParen => Kind::Bracket,
+        TokenKind::Meta => Kind::Meta,
+        TokenKind::Comment => Kind::Comment,
+        TokenKind::Unknown => Kind::Plain,
+    }
+}
+
+/// Split a line into classified spans
+///
+/// Whitespace isn't included in any span.
+pub fn scan(line: &str) -> Vec<Span> {
+    lexer::tokenize(line)
+        .iter()
+        .map(|token| Span {
+            kind: classify(line, token),
+            start: token.span.start,
+            end: token.span.end,
+        })
+        .collect()
+}
+
+/// Find the spans to emphasise for the cursor position
+///
+/// If the cursor is just after or on a bracket, that bracket and its partner
+/// are returned. If it's on either quote of a string, both quotes are
+/// returned (as one byte spans).
+fn emphasised(line: &str, spans: &[Span], pos: usize) -> Vec<usize> {
+    let at_cursor = |offset: usize| offset + 1 == pos || offset == pos;
+
+    // Pair up brackets, ignoring any inside strings
+    let mut stack = Vec::new();
+    for span in spans.iter().filter(|span| span.kind == Kind::Bracket) {
+        if line.as_bytes()[span.start] == b'(' {
+            stack.push(span.start);
+        } else if let Some(start) = stack.pop()
+            && (at_cursor(start) || at_cursor(span.start))
+        {
+            return vec![start, span.start];
+        }
+    }
+
+    spans
+        .iter()
+        .filter(|span| span.kind == Kind::String)
+        .find(|span| at_cursor(span.start) || at_cursor(span.end - 1))
+        .map(|span| vec![span.start, span.end - 1])
+        .unwrap_or_default()
+}
+
+/// Color a piece of text by its kind
+fn paint(text: &str, kind: Kind) -> String {
+    match kind {
+        Kind::Keyword => text.cyan().bold().to_string(),
+        Kind::Operator => text.yellow().to_string(),
+        Kind::String => text.green().to_string(),
+        Kind::Unterminated => text.red().to_string(),
+        Kind::Number => text.magenta().to_string(),
+        Kind::TimeRange => text.bright_magenta().to_string(),
+        Kind::Path => text.bright_blue().to_string(),
+        Kind::Meta => text.yellow().bold().to_string(),
+        Kind::Comment => text.bright_black().italic().to_string(),
+        Kind::Bracket | Kind::Plain => text.to_string(),
+    }
+}
+
+/// Highlight a line of input with the cursor at `pos`
+///
+/// Returns `None` when colors are turned off.
+pub fn highlight(line: &str, pos: usize) -> Option<String> {
+    if !colored::control::SHOULD_COLORIZE.should_colorize() {
+        return None;
+    }
+    let spans = scan(line);
+    let emphasis = emphasised(line, &spans, pos);
+    let mut output = String::with_capacity(line.len() * 2);
+    let mut last = 0;
+    for span in &spans {
+        output.push_str(&line[last..span.start]);
+        let text = &line[span.start..span.end];
+        if span.kind == Kind::String && emphasis.contains(&span.start) {
+            // Emphasise the quotes, keeping the contents green
+            let quote = &text[..1];
+            output.push_str(&quote.green().bold().reversed().to_string());
+            output.push_str(&paint(&text[1..text.len() - 1], Kind::String));
+            output.push_str(&quote.green().bold().reversed().to_string());
+        } else if emphasis.contains(&span.start) {
+            output.push_str(&text.bold().reversed().to_string());
+        } else {
+            output.push_str(&paint(text, span.kind));
+        }
+        last = span.end;
+    }
+    output.push_str(&line[last..]);
+    Some(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(line: &str) -> Vec<(&str, Kind)> {
+        scan(line)
+            .into_iter()
+            .map(|span| (&line[span.start..span.end], span.kind))
+            .collect()
+    }
+
+    #[test]
+    fn test_scan_statement() {
+        assert_eq!(
+            kinds(
+                "QUERY module_activity.** WITH module_activity.actor.process.file.name = 'regsvr32.exe' AFTER 1h"
+            ),
+            vec![
+                ("QUERY", Kind::Keyword),
+                ("module_activity.**", Kind::Path),
+                ("WITH", Kind::Keyword),
+                ("module_activity.actor.process.file.name", Kind::Path),
+                ("=", Kind::Operator),
+                ("'regsvr32.exe'", Kind::String),
+                ("AFTER", Kind::Keyword),
+                ("1h", Kind::TimeRange),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_scan_operators_and_literals() {
+        assert_eq!(
+            kinds(r#"x.pid >= 42 and (y != "a \"b\"")"#),
+            vec![
+                ("x.pid", Kind::Path),
+                (">=", Kind::Operator),
+                ("42", Kind::Number),
+                ("and", Kind::Keyword),
+                ("(", Kind::Bracket),
+                ("y", Kind::Plain),
+                ("!=", Kind::Operator),
+                (r#""a \"b\"""#, Kind::String),
+                (")", Kind::Bracket),
+            ]
+        );
+        assert_eq!(
+            kinds("\\format 'csv"),
+            vec![("\\format", Kind::Meta), ("'csv", Kind::Unterminated)]
+        );
+    }
+
+    #[test]
+    fn test_emphasis() {
+        let line = "QUERY (a = ')' OR (b = 1))";
+        let spans = scan(line);
+        // Cursor after the last bracket matches the first
+        assert_eq!(emphasised(line, &spans, line.len()), vec![6, 25]);
+        // Cursor on the inner opening bracket
+        assert_eq!(emphasised(line, &spans, 18), vec![18, 24]);
+        // Cursor on the closing quote of the string
+        assert_eq!(emphasised(line, &spans, 13), vec![11, 13]);
+        assert!(emphasised(line, &spans, 3).is_empty());
+    }
+}
@@ -0,0 +1,192 @@
+//! Cancelling slow work with Ctrl+C
+//!
+//! Work that should stop on Ctrl+C is run with [`run`], which waits for it on
+//! another thread. While it waits, a handler (for SIGINT on Unix, or the
+//! console's Ctrl+C event on Windows) records the interrupt instead of ending
+//! the process, and the wait gives up as soon as one is recorded. The
+//! previous handler is restored afterwards, so Ctrl+C anywhere else behaves
+//! as it always has. The blocking HTTP client can't be stopped part way
+//! through a request, so an abandoned request carries on in the background
+//! until it completes or times out (650 seconds by default, see
+//! `--timeout`), and its response is thrown away.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+/// How often to check for an interrupt while waiting
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Set by the Ctrl+C handler
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Records Ctrl+C in [`INTERRUPTED`], instead of letting it end the process,
+/// until dropped
+///
+/// Also used while a program such as the pager runs in the foreground, so
+/// that Ctrl+C only reaches the program.
+#[cfg(unix)]
+pub struct Handler {
+    previous: libc::sigaction,
+}
+
+#[cfg(unix)]
+impl Handler {
+    /// Install the handler, or return `None` if it can't be
+    pub fn install() -> Option<Self> {
+        extern "C" fn handle_sigint(_: libc::c_int) {
+            INTERRUPTED.store(true, Ordering::SeqCst);
+        }
+
+        // SAFETY: the handler only stores to an atomic, which is
+        // async-signal-safe, and sigaction is given fully initialised structs
+        unsafe {
+            let mut action: libc::sigaction = std::mem::zeroed();
+            action.sa_sigaction = handle_sigint as extern "C" fn(libc::c_int) as libc::sighandler_t;
+            action.sa_flags = libc::SA_RESTART;
+            libc::sigemptyset(&mut action.sa_mask);
+            let mut previous: libc::sigaction = std::mem::zeroed();
+            if libc::sigaction(libc::SIGINT, &action, &mut previous) != 0 {
+                return None;
+            }
+            Some(Self { previous })
+        }
+    }
+}
+
+#[cfg(unix)]
+impl Drop for Handler {
+    fn drop(&mut self) {
+        // SAFETY: previous was filled in by sigaction when installing
+        unsafe {
+            libc::sigaction(libc::SIGINT, &self.previous, std::ptr::null_mut());
+        }
+    }
+}
+
+/// On Windows, Ctrl+C is a console control event rather than a signal
+#[cfg(windows)]
+pub struct Handler;
+
+#[cfg(windows)]
+mod console {
+    /// `CTRL_C_EVENT` from wincon.h
+    pub const CTRL_C_EVENT: u32 = 0;
+
+    pub type HandlerRoutine = unsafe extern "system" fn(u32) -> i32;
+
+    #[link(name = "kernel32")]
+    unsafe extern "system" {
+        pub fn SetConsoleCtrlHandler(handler: Option<HandlerRoutine>, add: i32) -> i32;
+    }
+
+    /// Record Ctrl+C, and leave every other event (such as closing the
+    /// console) to the next handler
+    pub unsafe extern "system" fn handle_ctrl(event: u32) -> i32 {
+        if event == CTRL_C_EVENT {
+            super::INTERRUPTED.store(true, super::Ordering::SeqCst);
+            1
+        } else {
+            0
+        }
+    }
+}
+
+#[cfg(windows)]
+impl Handler {
+    /// Install the handler, or return `None` if it can't be
+    pub fn install() -> Option<Self> {
+        // SAFETY: the handler only stores to an atomic
+        let added = unsafe { console::SetConsoleCtrlHandler(Some(console::handle_ctrl), 1) };
+        (added != 0).then_some(Self)
+    }
+}
+
+#[cfg(windows)]
+impl Drop for Handler {
+    fn drop(&mut self) {
+        // SAFETY: removes the handler added by install
+        unsafe {
+            console::SetConsoleCtrlHandler(Some(console::handle_ctrl), 0);
+        }
+    }
+}
+
+/// Ctrl+C isn't handled on this platform, so it still ends the process
+#[cfg(not(any(unix, windows)))]
+pub struct Handler;
+
+#[cfg(not(any(unix, windows)))]
+impl Handler {
+    pub fn install() -> Option<Self> {
+        None
+    }
+}
+
+/// Run `work` on another thread and wait for it, unless Ctrl+C is pressed
+///
+/// Returns `None` if the wait was interrupted.
+pub fn run<T, F>(work: F) -> Option<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> T + Send + 'static,
+{
+    INTERRUPTED.store(false, Ordering::SeqCst);
+    let _handler = Handler::install();
+    wait(work, &INTERRUPTED)
+}
+
+/// Run `work` on another thread and wait for it, giving up when `interrupted`
+/// is set
+fn wait<T, F>(work: F, interrupted: &AtomicBool) -> Option<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> T + Send + 'static,
+{
+    let (sender, receiver) = mpsc::channel();
+    let handle = thread::spawn(move || {
+        // Nobody is listening any more if the wait was interrupted
+        let _ = sender.send(work());
+    });
+    loop {
+        match receiver.recv_timeout(POLL_INTERVAL) {
+            Ok(result) => return Some(result),
+            Err(mpsc::RecvTimeoutError::Timeout) => {
+                if interrupted.swap(false, Ordering::SeqCst) {
+                    return None;
+                }
+            }
+            // The work panicked before sending a result
+            Err(mpsc::RecvTimeoutError::Disconnected) => match handle.join() {
+                Err(panic) => std::panic::resume_unwind(panic),
+                Ok(()) => unreachable!("the result is sent before the thread ends"),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_wait() {
+        let interrupted = Arc::new(AtomicBool::new(false));
+        assert_eq!(wait(|| 42, &interrupted), Some(42));
+
+        let flag = Arc::clone(&interrupted);
+        let result = wait(
+            move || {
+                flag.store(true, Ordering::SeqCst);
+                thread::sleep(POLL_INTERVAL * 4);
+                42
+            },
+            &interrupted,
+        );
+        assert_eq!(result, None);
+        // The interrupt is used up by the wait it cancelled
+        assert!(!interrupted.load(Ordering::SeqCst));
+    }
+}
@@ -0,0 +1,321 @@
+//! Local inspection of JWT bearer tokens
+//!
+//! The payload of a JWT is decoded without verifying its signature - the
+//! server does that. It's only used to show who a token belongs to and to
+//! warn when it has expired, before a long running query is sent with it.
+
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use colored::Colorize;
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Claims that commonly hold the tenant, in order of preference
+const TENANT_CLAIMS: &[&str] = &["tenant", "tenant_id", "tenantId", "tid", "org_id"];
+
+/// The claims of a JWT payload that fsqlctl knows about
+#[derive(Debug, Default, Deserialize)]
+pub struct Claims {
+    pub sub: Option<String>,
+    pub iss: Option<String>,
+    /// Expiry time, in seconds since the Unix epoch
+    pub exp: Option<i64>,
+    /// Issue time, in seconds since the Unix epoch
+    pub iat: Option<i64>,
+    /// Every other claim
+    #[serde(flatten)]
+    pub other: Map<String, Value>,
+}
+
+impl Claims {
+    /// Get the tenant from whichever claim holds it
+    ///
+    /// Namespaced claims such as `https://example.com/tenant` are also
+    /// recognised.
+    pub fn tenant(&self) -> Option<String> {
+        let text = |value: &Value| match value {
+            Value::String(s) => Some(s.clone()),
+            Value::Number(n) => Some(n.to_string()),
+            _ => None,
+        };
+        TENANT_CLAIMS
+            .iter()
+            .find_map(|name| self.other.get(*name).and_then(text))
+            .or_else(|| {
+                self.other
+                    .iter()
+                    .filter(|(name, _)| name.ends_with("/tenant") || name.ends_with("/tenant_id"))
+                    .find_map(|(_, value)| text(value))
+            })
+    }
+
+    /// Seconds until the token expires, negative once it has expired
+    fn expires_in(&self, now: i64) -> Option<i64> {
+        self.exp.map(|exp| exp - now)
+    }
+}
+
+/// Decode the claims of a JWT without verifying it
+///
+/// Returns `None` for API keys or anything else that isn't a JWT with a JSON
+/// payload.
+pub fn decode(token: &str) -> Option<Claims> {
+    let token = token.strip_prefix("Bearer ").unwrap_or(token);
+    let mut parts = token.split('.');
+    let (_, payload, _) = (parts.next()?, parts.next()?, parts.next()?);
+    if parts.next().is_some() {
+        return None;
+    }
+    // Some issuers pad their segments even though the spec says not to
+    let bytes = URL_SAFE_NO_PAD.decode(payload.trim_end_matches('=')).ok()?;
+    serde_json::from_slice(&bytes).ok()
+}
+
+/// Seconds since the Unix epoch
+fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+/// Describe a number of seconds as a short duration such as `2h 5m`
+fn describe_duration(seconds: i64) -> String {
+    let seconds = seconds.abs();
+    let (days, hours, minutes) = (seconds / 86400, seconds % 86400 / 3600, seconds % 3600 / 60);
+    if days > 0 {
+        format!("{}d {}h", days, hours)
+    } else if hours > 0 {
+        format!("{}h {}m", hours, minutes)
+    } else if minutes > 0 {
+        format!("{}m", minutes)
+    } else {
+        format!("{}s", seconds)
+    }
+}
+
+/// Format a Unix timestamp as a UTC date and time
+fn format_timestamp(timestamp: i64) -> String {
+    // Convert days since the epoch to a civil date (Howard Hinnant's algorithm)
+    let days = timestamp.div_euclid(86400);
+    let seconds = timestamp.rem_euclid(86400);
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02} UTC",
+        year,
+        month,
+        day,
+        seconds / 3600,
+        seconds % 3600 / 60,
+        seconds % 60
+    )
+}
+
+/// How close a token is to expiring, in order of seriousness
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Expiry {
+    Soon = 1,
+    Past = 2,
+}
+
+/// The most serious expiry warned about so far, or 0 for none
+static WARNED: AtomicU8 = AtomicU8::new(0);
+
+/// A warning about a token that has expired or is about to
+///
+/// `window` is how far ahead of expiry to start warning.
+fn expiry_warning_at(claims: &Claims, now: i64, window: Duration) -> Option<(Expiry, String)> {
+    let remaining = claims.expires_in(now)?;
+    if remaining <= 0 {
+        Some((
+            Expiry::Past,
+            format!(
+                "Token expired {} ago ({}) - requests will be rejected",
+                describe_duration(remaining),
+                format_timestamp(claims.exp?)
+            ),
+        ))
+    } else if remaining <= window.as_secs() as i64 {
+        Some((
+            Expiry::Soon,
+            format!(
+                "Token expires in {} ({})",
+                describe_duration(remaining),
+                format_timestamp(claims.exp?)
+            ),
+        ))
+    } else {
+        None
+    }
+}
+
+/// Whether an expiry is more serious than any warned about so far, noting
+/// that it has now been warned about
+fn first_warning(warned: &AtomicU8, expiry: Expiry) -> bool {
+    warned.fetch_max(expiry as u8, Ordering::SeqCst) < expiry as u8
+}
+
+/// Warn if a token has expired or expires within `window`
+///
+/// Called before each command is sent. A warning is only printed when the
+/// token's state changes, from valid to expiring soon or to expired, so
+/// scripts and long REPL sessions don't repeat it for every command. API keys
+/// and JWTs without an expiry never produce a warning.
+pub fn warn_before_dispatch(token: &str, window: Duration) {
+    if let Some((expiry, warning)) =
+        decode(token).and_then(|claims| expiry_warning_at(&claims, now(), window))
+        && first_warning(&WARNED, expiry)
+    {
+        eprintln!("⚠️  {}", warning);
+    }
+}
+
+/// Print what a token says about its owner and expiry
+///
+/// Returns false if the token is a JWT that has already expired.
+pub fn print_whoami(token: &str) -> bool {
+    let Some(claims) = decode(token) else {
+        println!("🔑 {} API key", "Token type:".cyan());
+        println!("   API keys don't carry any details that can be shown locally");
+        return true;
+    };
+
+    let unknown = || "(unknown)".dimmed().to_string();
+    println!("🔑 {} JWT", "Token type:".cyan());
+    println!(
+        "   {} {}",
+        "Subject:".cyan(),
+        claims.sub.clone().unwrap_or_else(unknown)
+    );
+    println!(
+        "   {}  {}",
+        "Tenant:".cyan(),
+        claims.tenant().unwrap_or_else(unknown)
+    );
+    println!(
+        "   {}  {}",
+        "Issuer:".cyan(),
+        claims.iss.clone().unwrap_or_else(unknown)
+    );
+    if let Some(iat) = claims.iat {
+        println!("   {}  {}", "Issued:".cyan(), format_timestamp(iat));
+    }
+
+    let now = now();
+    match (claims.exp, claims.expires_in(now)) {
+        (Some(exp), Some(remaining)) if remaining <= 0 => {
+            println!(
+                "   {} {} ({})",
+                "Expires:".cyan(),
+                format_timestamp(exp),
+                format!("expired {} ago", describe_duration(remaining)).red()
+            );
+            false
+        }
+        (Some(exp), Some(remaining)) => {
+            println!(
+                "   {} {} ({})",
+                "Expires:".cyan(),
+                format_timestamp(exp),
+                format!("in {}", describe_duration(remaining)).green()
+            );
+            true
+        }
+        _ => {
+            println!("   {} never", "Expires:".cyan());
+            true
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn token(payload: &str) -> String {
+        format!(
+            "eyJhbGciOiJIUzI1NiJ9.{}.c2lnbmF0dXJl",
+            URL_SAFE_NO_PAD.encode(payload)
+        )
+    }
+
+    #[test]
+    fn test_decode_claims() {
+        let claims = decode(&token(
+            r#"{"sub": "analyst@example.com", "iss": "https://auth.query.ai", "exp": 1700000000, "tenant_id": 42}"#,
+        ))
+        .unwrap();
+        assert_eq!(claims.sub.as_deref(), Some("analyst@example.com"));
+        assert_eq!(claims.iss.as_deref(), Some("https://auth.query.ai"));
+        assert_eq!(claims.exp, Some(1700000000));
+        assert_eq!(claims.tenant().as_deref(), Some("42"));
+
+        let claims = decode(&format!(
+            "Bearer {}",
+            token(r#"{"https://query.ai/tenant": "acme"}"#)
+        ))
+        .unwrap();
+        assert_eq!(claims.tenant().as_deref(), Some("acme"));
+        assert_eq!(claims.sub, None);
+
+        assert!(decode("not-a-jwt").is_none());
+        assert!(decode("a.b.c").is_none());
+        assert!(decode(&format!("{}.extra", token("{}"))).is_none());
+    }
+
+    #[test]
+    fn test_expiry_warning() {
+        let window = Duration::from_secs(600);
+        let claims = Claims {
+            exp: Some(1_000_000),
+            ..Default::default()
+        };
+        assert_eq!(expiry_warning_at(&claims, 1_000_000 - 3600, window), None);
+        assert_eq!(
+            expiry_warning_at(&claims, 1_000_000 - 300, window),
+            Some((
+                Expiry::Soon,
+                "Token expires in 5m (1970-01-12 13:46:40 UTC)".to_string()
+            ))
+        );
+        assert_eq!(
+            expiry_warning_at(&claims, 1_000_000 + 7500, window),
+            Some((
+                Expiry::Past,
+                "Token expired 2h 5m ago (1970-01-12 13:46:40 UTC) - requests will be rejected"
+                    .to_string()
+            ))
+        );
+        assert_eq!(
+            expiry_warning_at(&Claims::default(), 1_000_000, window),
+            None
+        );
+    }
+
+    #[test]
+    fn test_first_warning() {
+        let warned = AtomicU8::new(0);
+        assert!(first_warning(&warned, Expiry::Soon));
+        assert!(!first_warning(&warned, Expiry::Soon));
+        assert!(first_warning(&warned, Expiry::Past));
+        assert!(!first_warning(&warned, Expiry::Past));
+        assert!(!first_warning(&warned, Expiry::Soon));
+    }
+
+    #[test]
+    fn test_format_timestamp() {
+        assert_eq!(format_timestamp(0), "1970-01-01 00:00:00 UTC");
+        assert_eq!(format_timestamp(951782400), "2000-02-29 00:00:00 UTC");
+        assert_eq!(format_timestamp(1792195200), "2026-10-17 00:00:00 UTC");
+    }
+}
@@ -0,0 +1,468 @@
+//! The `fsqlctl lint` subcommand
+//!
+//! Checks FSQL files for queries that are valid but likely to be slow,
+//! expensive or wrong, before they're sent to the API. Each rule can be
+//! turned off under `[lint.rules]` in the config file. Diagnostics are
+//! printed one per line, or as a JSON array with `--format json`.
+//!
+//! Attribute paths are checked against a schema file (the output of
+//! `fsqlctl -c "EXPLAIN SCHEMA *"`) when one is available; without one the
+//! `unknown-attribute` rule does nothing.
+
+use crate::config::{Config, LintConfig};
+use crate::fmt;
+use crate::fsql::Span;
+use crate::fsql::ast::{Clause, Condition, Explain, Operator, Query, Statement, Value};
+use crate::fsql::format::format_condition;
+use crate::fsql::script;
+use crate::schema::SchemaCache;
+use crate::stdio;
+use clap::ValueEnum;
+use colored::Colorize;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+
+/// Names of the rules that can be turned off
+pub const RULES: &[&str] = &[
+    "missing-time-bound",
+    "broad-wildcard",
+    "case-sensitive-comparison",
+    "duplicate-condition",
+    "unknown-attribute",
+];
+
+/// Attributes whose values are usually lower case, matched as path suffixes
+const DEFAULT_LOWERCASE_FIELDS: &[&str] = &[
+    "hostname",
+    "domain",
+    "email_addr",
+    "file.name",
+    "process.name",
+];
+
+/// Classes with more attributes than this are too large to select with `**`
+const WILDCARD_LIMIT: usize = 200;
+
+/// Operators that compare text exactly
+const CASE_SENSITIVE_OPERATORS: &[Operator] = &[
+    Operator::Eq,
+    Operator::Ne,
+    Operator::In,
+    Operator::Contains,
+    Operator::StartsWith,
+    Operator::EndsWith,
+];
+
+/// How diagnostics are printed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum LintFormat {
+    /// One `file:line:column: severity[rule]: message` line each
+    Text,
+    /// A JSON array of objects
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// A problem found in a file
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Diagnostic {
+    pub file: String,
+    pub line: usize,
+    pub column: usize,
+    pub severity: Severity,
+    /// Name of the rule, or `syntax` for a parse error
+    pub rule: &'static str,
+    pub message: String,
+}
+
+/// Runs the enabled rules over FSQL source
+pub struct Linter {
+    config: LintConfig,
+    schema: Option<SchemaCache>,
+}
+
+impl Linter {
+    pub fn new(config: LintConfig, schema: Option<SchemaCache>) -> Self {
+        Self { config, schema }
+    }
+
+    /// Lint each statement of a script, naming the file it came from in the
+    /// diagnostics
+    pub fn lint(&self, file: &str, source: &str) -> Vec<Diagnostic> {
+        let mut found = Vec::new();
+        let mut report = |rule: &'static str, severity, span: Span, message: String| {
+            if rule == "syntax" || self.config.enabled(rule) {
+                found.push(Diagnostic {
+                    file: file.to_string(),
+                    line: span.line,
+                    column: span.column,
+                    severity,
+                    rule,
+                    message,
+                });
+            }
+        };
+
+        let pieces = script::split(source);
+        for piece in pieces.iter().filter(|piece| piece.statement.is_some()) {
+            match piece.parse(source) {
+                Ok(statement) => {
+                    for query in queries(&statement) {
+                        self.check_query(query, &mut report);
+                    }
+                }
+                Err(e) => report("syntax", Severity::Error, e.span, e.message),
+            }
+        }
+        found
+    }
+
+    fn check_query(
+        &self,
+        query: &Query,
+        report: &mut impl FnMut(&'static str, Severity, Span, String),
+    ) {
+        if !query.is_time_bounded() {
+            report(
+                "missing-time-bound",
+                Severity::Warning,
+                query.span,
+                "query has no AFTER or BEFORE clause, so it searches all time".to_string(),
+            );
+        }
+
+        for path in &query.selections {
+            if let Some(class) = path.text.strip_suffix(".**")
+                && let Some(reason) = self.huge(class)
+            {
+                report(
+                    "broad-wildcard",
+                    Severity::Warning,
+                    path.span,
+                    format!(
+                        "'{}' selects every attribute of {}, {}; select only the attributes you need",
+                        path.text, class, reason
+                    ),
+                );
+            }
+        }
+
+        let conditions = query.clauses.iter().filter_map(|clause| match clause {
+            Clause::With(condition) => Some(condition),
+            _ => None,
+        });
+        for condition in conditions {
+            for comparison in condition.comparisons() {
+                if CASE_SENSITIVE_OPERATORS.contains(&comparison.operator)
+                    && self.usually_lowercase(&comparison.path.text)
+                    && has_uppercase(&comparison.value)
+                {
+                    report(
+                        "case-sensitive-comparison",
+                        Severity::Warning,
+                        comparison.span,
+                        format!(
+                            "{} is usually lower case but is compared with upper case text, and {} is case sensitive",
+                            comparison.path.text,
+                            comparison.operator.as_str()
+                        ),
+                    );
+                }
+            }
+            duplicates(condition, report);
+        }
+
+        if let Some(schema) = &self.schema {
+            let paths = query
+                .selections
+                .iter()
+                .chain(query.clauses.iter().flat_map(|clause| {
+                    match clause {
+                        Clause::With(condition) => condition
+                            .comparisons()
+                            .into_iter()
+                            .map(|comparison| &comparison.path)
+                            .collect(),
+                        _ => Vec::new(),
+                    }
+                }));
+            for path in paths {
+                if !schema.is_known(&path.text) {
+                    report(
+                        "unknown-attribute",
+                        Severity::Error,
+                        path.span,
+                        format!("'{}' isn't in the schema", path.text),
+                    );
+                }
+            }
+        }
+    }
+
+    /// Why a class is too large to select with `**`, if it is
+    fn huge(&self, class: &str) -> Option<String> {
+        if self
+            .config
+            .huge_classes
+            .iter()
+            .any(|huge| huge.eq_ignore_ascii_case(class))
+        {
+            return Some("which is listed as a huge class".to_string());
+        }
+        let count = self
+            .schema
+            .as_ref()
+            .map_or(0, |schema| schema.attribute_count(class));
+        (count > WILDCARD_LIMIT).then(|| format!("which has {} attributes", count))
+    }
+
+    /// Whether an attribute's values are usually lower case
+    fn usually_lowercase(&self, path: &str) -> bool {
+        let path = path.to_lowercase();
+        let matches = |field: &str| {
+            let field = field.to_lowercase();
+            path == field || path.ends_with(&format!(".{}", field))
+        };
+        match &self.config.lowercase_fields {
+            Some(fields) => fields.iter().any(|field| matches(field)),
+            None => DEFAULT_LOWERCASE_FIELDS.iter().any(|field| matches(field)),
+        }
+    }
+}
+
+/// The queries within a statement
+fn queries(statement: &Statement) -> Vec<&Query> {
+    match statement {
+        Statement::Query(query) => vec![query],
+        Statement::Summarize(summarize) => vec![&summarize.query],
+        Statement::Validate(inner)
+        | Statement::Explain(
+            Explain::Attributes(inner) | Explain::Graphql(inner) | Explain::Statement(inner),
+        ) => queries(inner),
+        Statement::Explain(_) => Vec::new(),
+    }
+}
+
+/// Whether a quoted string (or any in a list) contains upper case letters
+fn has_uppercase(value: &Value) -> bool {
+    match value {
+        Value::String(text) => text.chars().any(char::is_uppercase),
+        Value::List(values) => values.iter().any(has_uppercase),
+        _ => false,
+    }
+}
+
+/// Report conditions repeated within the same AND or OR chain
+fn duplicates(
+    condition: &Condition,
+    report: &mut impl FnMut(&'static str, Severity, Span, String),
+) {
+    match condition {
+        Condition::And(conditions) | Condition::Or(conditions) => {
+            let mut seen: HashMap<String, Span> = HashMap::new();
+            for condition in conditions {
+                let text = format_condition(condition);
+                let span = first_span(condition);
+                if let Some(first) = seen.get(&text) {
+                    report(
+                        "duplicate-condition",
+                        Severity::Warning,
+                        span,
+                        format!(
+                            "'{}' is repeated from line {}, column {}",
+                            text, first.line, first.column
+                        ),
+                    );
+                } else {
+                    seen.insert(text, span);
+                }
+                duplicates(condition, report);
+            }
+        }
+        Condition::Not(condition) | Condition::Group(condition) => duplicates(condition, report),
+        Condition::Comparison(_) => {}
+    }
+}
+
+/// Where a condition starts, for reporting
+fn first_span(condition: &Condition) -> Span {
+    condition
+        .comparisons()
+        .first()
+        .map(|comparison| comparison.span)
+        .unwrap_or_default()
+}
+
+/// Find the schema to check attributes against
+///
+/// A file given on the command line must load; the configured or default
+/// schema file is used if it exists.
+fn load_schema(given: Option<&Path>, config: &LintConfig) -> Option<SchemaCache> {
+    if let Some(path) = given {
+        return match SchemaCache::from_file(path) {
+            Ok(schema) => Some(schema),
+            Err(e) => {
+                eprintln!("{}", format!("❌ {}", e).red());
+                std::process::exit(1);
+            }
+        };
+    }
+    let path = config
+        .schema
+        .clone()
+        .unwrap_or_else(SchemaCache::default_file);
+    if !path.exists() {
+        return None;
+    }
+    match SchemaCache::from_file(&path) {
+        Ok(schema) => Some(schema),
+        Err(e) => {
+            eprintln!("⚠️  Warning: {}", e);
+            None
+        }
+    }
+}
+
+fn print_text(diagnostics: &[Diagnostic]) {
+    for diagnostic in diagnostics {
+        let severity = match diagnostic.severity {
+            Severity::Error => "error".red().bold(),
+            Severity::Warning => "warning".yellow().bold(),
+        };
+        println!(
+            "{}:{}:{}: {}[{}]: {}",
+            diagnostic.file,
+            diagnostic.line,
+            diagnostic.column,
+            severity,
+            diagnostic.rule,
+            diagnostic.message
+        );
+    }
+}
+
+/// Run `fsqlctl lint`, exiting with a non-zero status if anything is found
+///
+/// Reads stdin when no files are given.
+pub fn handle_lint(paths: &[PathBuf], schema: Option<&Path>, format: LintFormat) {
+    let config = Config::load().unwrap_or_else(|e| {
+        eprintln!("⚠️  Warning: Failed to load config file: {}", e);
+        Config::default()
+    });
+    let schema = load_schema(schema, &config.lint);
+    let linter = Linter::new(config.lint, schema);
+
+    let mut diagnostics = Vec::new();
+    if paths.is_empty() {
+        if std::io::stdin().is_terminal() {
+            eprintln!("{}", "❌ Give files to lint or pipe FSQL to stdin".red());
+            std::process::exit(1);
+        }
+        diagnostics.extend(linter.lint("<stdin>", &stdio::read_stdin()));
+    } else {
+        for path in fmt::expand_paths(paths) {
+            let file = path.display().to_string();
+            diagnostics.extend(linter.lint(&file, &stdio::read_file(&file)));
+        }
+    }
+
+    match format {
+        LintFormat::Text => print_text(&diagnostics),
+        LintFormat::Json => match serde_json::to_string_pretty(&diagnostics) {
+            Ok(json) => println!("{}", json),
+            Err(e) => eprintln!("❌ Failed to write diagnostics: {}", e),
+        },
+    }
+    if !diagnostics.is_empty() {
+        std::process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(linter: &Linter, source: &str) -> Vec<(&'static str, usize, usize)> {
+        linter
+            .lint("test.fsql", source)
+            .into_iter()
+            .map(|diagnostic| (diagnostic.rule, diagnostic.line, diagnostic.column))
+            .collect()
+    }
+
+    #[test]
+    fn test_lint_rules() {
+        let linter = Linter::new(
+            LintConfig {
+                huge_classes: vec!["network_activity".to_string()],
+                ..LintConfig::default()
+            },
+            None,
+        );
+        assert!(
+            rules(
+                &linter,
+                "QUERY module_activity.** WITH module_activity.activity_id = LOAD AFTER 1h"
+            )
+            .is_empty()
+        );
+        assert_eq!(
+            rules(
+                &linter,
+                "QUERY network_activity.**\nWITH network_activity.src_endpoint.hostname = 'DC01'\n  AND (x.a = 1 OR x.a = 1)"
+            ),
+            vec![
+                ("missing-time-bound", 1, 1),
+                ("broad-wildcard", 1, 7),
+                ("case-sensitive-comparison", 2, 6),
+                ("duplicate-condition", 3, 19),
+            ]
+        );
+        assert_eq!(
+            rules(&linter, "QUERY a.** WITH;\nQUERY b.* AFTER 1h;\nQUERY c.*"),
+            vec![("syntax", 1, 16), ("missing-time-bound", 3, 1)]
+        );
+        // Only quoted text is checked for case; enum values are upper case
+        assert_eq!(
+            rules(&linter, "QUERY a.b WITH a.file.name IN ('x', 'Y') AFTER 1h"),
+            vec![("case-sensitive-comparison", 1, 16)]
+        );
+    }
+
+    #[test]
+    fn test_lint_schema_and_disabled_rules() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("schema.json");
+        std::fs::write(&path, r#"{"module_activity": {"activity_id": "integer"}}"#).unwrap();
+
+        let mut config = LintConfig::default();
+        config.rules.insert("missing-time-bound".to_string(), false);
+        let linter = Linter::new(config, SchemaCache::from_file(&path).ok());
+        let diagnostics = linter.lint(
+            "test.fsql",
+            "QUERY module_activity.** WITH module_activity.actor = 1",
+        );
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].rule, "unknown-attribute");
+        assert_eq!(diagnostics[0].severity, Severity::Error);
+        assert_eq!(
+            serde_json::to_value(&diagnostics[0]).unwrap(),
+            serde_json::json!({
+                "file": "test.fsql",
+                "line": 1,
+                "column": 31,
+                "severity": "error",
+                "rule": "unknown-attribute",
+                "message": "'module_activity.actor' isn't in the schema"
+            })
+        );
+    }
+}
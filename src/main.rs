@@ -26,14 +26,18 @@
 //! 🔗 API: https://api.query.ai/search/translation/fsql
 //! ================================================================================
 //! 📚 FSQL REPL Help:
-//!    EXPLAIN CONNECTORS         - Get details about all configured connectors
-//!    EXPLAIN VERSION            - List FSQL and QDM versions
-//!    EXPLAIN ATTRIBUTES <fsql>  - Get a list of explanded attributes
-//!    EXPLAIN <fsql>             - Get query execution details
+//!    QUERY <fsql>                - Execute a query and show the results
+//!    VALIDATE <fsql>             - Check whether a query has valid syntax
+//!    SUMMARIZE <fsql>            - Get summary information about a query
+//!    EXPLAIN CONNECTORS          - Get details about all configured connectors
+//!    EXPLAIN VERSION             - List FSQL and QDM versions
+//!    EXPLAIN SCHEMA <path>       - Describe the schema for the given path
+//!    EXPLAIN ATTRIBUTES <fsql>   - Get a list of explanded attributes
+//!    EXPLAIN <fsql>              - Get query execution details
 //!    EXPLAIN GRAPHQL <fsql>      - Show the graphql translation of the given FSQL
-//!    help, h                    - Show this help message
-//!    clear                      - Clear the screen
-//!    exit                       - Exit the REPL
+//!    help, h                     - Show this help message
+//!    clear                       - Clear the screen
+//!    exit                        - Exit the REPL
 //! fsql>
 //! ```
 //!
@@ -68,36 +72,89 @@
 //! ```
 
 ////////////////////////////////////////////////////////////////////////////////
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use colored::Colorize;
 use std::io::IsTerminal;
 
 mod api;
+mod columnar;
+mod command;
+mod completion;
 mod config;
+mod editor;
+mod fmt;
+mod fsql;
+mod helper;
+mod highlight;
+mod interrupt;
+mod jwt;
+mod lint;
+mod output;
+mod pager;
+mod queries;
 mod repl;
+mod schema;
+mod secrets;
+mod settings;
 mod stdio;
+mod vars;
+mod viewer;
 
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
 pub struct Args {
+    #[command(subcommand)]
+    pub subcommand: Option<Commands>,
+
     #[arg(
         help = "Bearer token or API key for authentication",
         env = "FSQL_TOKEN"
     )]
     pub token: Option<String>,
 
-    #[arg(long, default_value = "api.query.ai", help = "Hostname for FSQL API")]
-    pub host: String,
+    #[arg(long, help = "Hostname for FSQL API [default: api.query.ai]")]
+    pub host: Option<String>,
+
+    #[arg(long, help = "Path to endpoint [default: search/translation/fsql]")]
+    pub path: Option<String>,
+
+    #[arg(long, help = "Port number for the API [default: 443]")]
+    pub port: Option<u16>,
+
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        help = "Total time allowed for a request [default: 650]"
+    )]
+    pub timeout: Option<u64>,
+
+    #[arg(
+        long,
+        value_name = "MINUTES",
+        default_value_t = 10,
+        help = "Warn before sending a command when the token expires within this many minutes"
+    )]
+    pub expiry_warning: u64,
+
+    #[arg(
+        long,
+        env = "FSQL_PROFILE",
+        help = "Name of the connection profile to use from the config file"
+    )]
+    pub profile: Option<String>,
 
     #[arg(
         long,
-        default_value = "search/translation/fsql",
-        help = "Path to endpoint"
+        env = "FSQL_NO_SAVE_TOKEN",
+        help = "Don't save a token given on the command line to the config file"
     )]
-    pub path: String,
+    pub no_save_token: bool,
 
-    #[arg(long, default_value_t = 443, help = "Port number for the API")]
-    pub port: u16,
+    #[arg(
+        long,
+        help = "Check the syntax of the command locally without contacting the API"
+    )]
+    pub offline_validate: bool,
 
     #[arg(short, long, help = "Enable verbose output for debugging")]
     pub verbose: bool,
@@ -117,16 +174,248 @@ pub struct Args {
         conflicts_with = "file"
     )]
     pub command: Option<String>,
+
+    #[arg(long, help = "Keep running the statements of a script after one fails")]
+    pub continue_on_error: bool,
+
+    #[arg(
+        long,
+        value_name = "NAME=VALUE",
+        global = true,
+        help = "Set a variable to substitute for ${NAME} or :NAME placeholders (repeatable)"
+    )]
+    pub var: Vec<String>,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        global = true,
+        help = "Read variables from a TOML file of name = value pairs"
+    )]
+    pub vars_file: Option<std::path::PathBuf>,
+
+    #[arg(
+        short,
+        long,
+        value_enum,
+        help = "Output format for QUERY results [default: table in the REPL, json otherwise]"
+    )]
+    pub output: Option<output::OutputFormat>,
+
+    #[arg(
+        long,
+        value_delimiter = ',',
+        help = "Comma separated list of result columns to show (e.g. time,actor.user.name)"
+    )]
+    pub columns: Option<Vec<String>>,
+
+    #[arg(
+        long,
+        help = "Explore QUERY results in a full-screen viewer instead of printing them"
+    )]
+    pub tui: bool,
+
+    #[arg(
+        long,
+        help = "Wrap each NDJSON result with the search_id and trace_id of the query"
+    )]
+    pub with_metadata: bool,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "Write QUERY results to a file instead of stdout (required for parquet and arrow in the REPL)"
+    )]
+    pub out_file: Option<std::path::PathBuf>,
+
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = output::ExistingFile::Overwrite,
+        help = "What to do when the --out-file already exists"
+    )]
+    pub if_exists: output::ExistingFile,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Commands {
+    #[command(
+        subcommand,
+        about = "Inspect or change settings and stored tokens in the config file"
+    )]
+    Config(settings::ConfigCommand),
+
+    #[command(about = "Show the subject, tenant, issuer and expiry of the token")]
+    Whoami,
+
+    #[command(about = "Format FSQL files in place, or stdin to stdout")]
+    Fmt {
+        #[arg(help = "Files to format, or directories to search for .fsql files")]
+        files: Vec<std::path::PathBuf>,
+
+        #[arg(
+            long,
+            help = "Don't write anything; exit with an error if any file would be reformatted"
+        )]
+        check: bool,
+    },
+
+    #[command(about = "Check FSQL files for risky queries before they're sent")]
+    Lint {
+        #[arg(help = "Files to check, or directories to search for .fsql files")]
+        files: Vec<std::path::PathBuf>,
+
+        #[arg(
+            long,
+            value_name = "PATH",
+            help = "Schema file to check attributes against (output of EXPLAIN SCHEMA *)"
+        )]
+        schema: Option<std::path::PathBuf>,
+
+        #[arg(long, value_enum, default_value_t = lint::LintFormat::Text, help = "How to print diagnostics")]
+        format: lint::LintFormat,
+    },
+
+    #[command(about = "Run a saved query from the query library, or list them")]
+    Run {
+        #[arg(help = "Name of the saved query; lists saved queries when not given")]
+        name: Option<String>,
+
+        #[arg(long, help = "Only list saved queries with this tag")]
+        tag: Option<String>,
+    },
+}
+
+const DEFAULT_HOST: &str = "api.query.ai";
+const DEFAULT_PATH: &str = "search/translation/fsql";
+const DEFAULT_PORT: u16 = 443;
+
+impl Args {
+    /// Hostname of the FSQL API
+    pub fn host(&self) -> &str {
+        self.host.as_deref().unwrap_or(DEFAULT_HOST)
+    }
+
+    /// Build the full URL of the FSQL API endpoint
+    pub fn api_url(&self) -> String {
+        format!(
+            "https://{}:{}/{}",
+            self.host(),
+            self.port.unwrap_or(DEFAULT_PORT),
+            self.path.as_deref().unwrap_or(DEFAULT_PATH)
+        )
+    }
+
+    /// Fill in any settings not given on the command line from a profile
+    fn apply_profile(&mut self, profile: &config::Profile) {
+        self.host = self.host.take().or_else(|| profile.host.clone());
+        self.port = self.port.or(profile.port);
+        self.path = self.path.take().or_else(|| profile.path.clone());
+        self.output = self.output.or(profile.output);
+        self.timeout = self.timeout.or(profile.timeout);
+    }
 }
 
 fn main() {
-    let args = Args::parse();
+    let mut args = Args::parse();
+
+    // The config, fmt and lint subcommands, and listing saved queries, don't
+    // need a token
+    let subcommand = match args.subcommand.take() {
+        Some(Commands::Config(command)) => {
+            settings::handle_config(command);
+            return;
+        }
+        Some(Commands::Fmt { files, check }) => {
+            fmt::handle_fmt(&files, check);
+            return;
+        }
+        Some(Commands::Lint {
+            files,
+            schema,
+            format,
+        }) => {
+            lint::handle_lint(&files, schema.as_deref(), format);
+            return;
+        }
+        Some(Commands::Run { name: None, tag }) => {
+            queries::print_list(tag.as_deref());
+            return;
+        }
+        subcommand => subcommand,
+    };
+
+    // Offline validation doesn't need a token either
+    if args.offline_validate {
+        let input = if let Some(command) = &args.command {
+            command.clone()
+        } else if let Some(file_path) = &args.file {
+            stdio::read_file(file_path)
+        } else if !std::io::stdin().is_terminal() {
+            stdio::read_stdin()
+        } else {
+            eprintln!(
+                "{}",
+                "❌ --offline-validate needs a command from -c, -f or stdin".red(),
+            );
+            std::process::exit(1);
+        };
+        stdio::validate_offline(&input, &load_variables(&args));
+        return;
+    }
+
+    // Load existing config
+    let mut config = match config::Config::load() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("⚠️  Warning: Failed to load config file: {}", e);
+            eprintln!("   Using default configuration");
+            config::Config::default()
+        }
+    };
+    if let Some(warning) = config::Config::permission_warning() {
+        eprintln!("⚠️  Warning: {}", warning);
+    }
+
+    // Settings on the command line take precedence over the selected profile
+    let profile = match config.select_profile(args.profile.as_deref()) {
+        Ok(profile) => profile.cloned(),
+        Err(e) => {
+            eprintln!("{}", format!("❌ {}", e).red());
+            std::process::exit(1);
+        }
+    };
+    if let Some(profile) = &profile {
+        args.apply_profile(profile);
+    }
+
+    // Resolve token from CLI/env, profile or config file
+    let token = resolve_token(&args, profile.as_ref(), &mut config);
+    let connection = api::Connection {
+        url: args.api_url(),
+        token,
+        timeout: args
+            .timeout
+            .map(std::time::Duration::from_secs)
+            .unwrap_or(api::DEFAULT_TIMEOUT),
+        expiry_warning: std::time::Duration::from_secs(args.expiry_warning * 60),
+        verbose: args.verbose,
+    };
 
-    // Resolve token from CLI/env or config file & update args
-    let token = resolve_token(&args);
+    if let Some(Commands::Whoami) = subcommand {
+        let valid = jwt::print_whoami(&connection.token);
+        std::process::exit(if valid { 0 } else { 1 });
+    }
+
+    let variables = load_variables(&args);
 
-    // Check for explicit input methods, then piped input, then REPL
-    if let Some(command) = args.command.clone() {
+    // Check for a saved query, explicit input methods, then piped input, then REPL
+    if let Some(Commands::Run {
+        name: Some(name), ..
+    }) = subcommand
+    {
+        run_saved_query(&name, &connection, &args, &variables);
+    } else if let Some(command) = args.command.clone() {
         if !std::io::stdin().is_terminal() {
             eprintln!(
                 "{}",
@@ -134,8 +423,7 @@ fn main() {
             );
             std::process::exit(1);
         }
-        let api_url = format!("https://{}/{}", args.host, args.path);
-        stdio::process_command(&command, &api_url, &token, args.verbose);
+        stdio::process_script(&command, &connection, &args, &variables);
     } else if let Some(file_path) = args.file.clone() {
         if !std::io::stdin().is_terminal() {
             eprintln!(
@@ -144,59 +432,55 @@ fn main() {
             );
             std::process::exit(1);
         }
-        stdio::handle_file(args, &token, &file_path);
+        stdio::handle_file(args, &connection, &variables, &file_path);
     } else if !std::io::stdin().is_terminal() {
-        stdio::handle_stdin(args, &token);
+        stdio::handle_stdin(args, &connection, &variables);
     } else {
-        repl::handle_repl(args, &token);
+        repl::handle_repl(
+            args,
+            &connection,
+            variables,
+            config.pager.unwrap_or_default(),
+        );
     }
 }
 
-/// Resolve the API token from CLI argument, environment variable, or config file
-/// Save the token to config if provided via CLI/env
-fn resolve_token(args: &Args) -> String {
-    // Load existing config
-    let mut config = match config::Config::load() {
-        Ok(config) => config,
-        Err(e) => {
-            eprintln!("⚠️  Warning: Failed to load config file: {}", e);
-            eprintln!("   Using default configuration");
-            config::Config::default()
-        }
-    };
+/// Resolve the API token from CLI argument, environment variable, profile, or config file
+/// Save the token to config if provided via CLI/env, unless --no-save-token is given
+fn resolve_token(
+    args: &Args,
+    profile: Option<&config::Profile>,
+    config: &mut config::Config,
+) -> String {
+    let host = args.host();
 
     // Check if token was provided via CLI or environment
     if let Some(provided_token) = &args.token {
-        // Token was provided - save it to config for this host
-        config.set_token(&args.host, provided_token);
-
-        // Save updated config
-        if let Err(e) = config.save() {
-            eprintln!("⚠️  Warning: Failed to save token to config: {}", e);
-        } else if args.verbose {
-            eprintln!(
-                "💾 Token saved to config for host '{}' at: {}",
-                args.host,
-                config::Config::get_config_location()
-            );
+        let passphrase = std::env::var(secrets::PASSPHRASE_ENV).ok();
+        let saved = config.get_token(host).is_some_and(|stored| {
+            secrets::holds_token(stored, provided_token, passphrase.as_deref())
+        });
+        if !args.no_save_token && !saved {
+            save_token(args, config, provided_token);
         }
-
         provided_token.clone()
+    } else if let Some(profile_token) = profile.and_then(|profile| profile.token.as_ref()) {
+        if args.verbose {
+            eprintln!("🔑 Using token from profile");
+        }
+        reveal_token(profile_token)
     } else {
         // No token provided - try to load from config
-        if let Some(stored_token) = config.get_token(&args.host) {
+        if let Some(stored_token) = config.get_token(host) {
             if args.verbose {
-                eprintln!(
-                    "🔑 Using stored token for host '{}' from config file",
-                    args.host
-                );
+                eprintln!("🔑 Using stored token for host '{}' from config file", host);
             }
-            stored_token.clone()
+            reveal_token(stored_token)
         } else {
             eprintln!("{}",
                 format!(
                     "❌ No API token found for host '{}'.\n   Provide a token via:\n   • Command line argument: fsqlctl <TOKEN> ...\n   • Environment variable: FSQL_TOKEN=<TOKEN>\n   • Config file at: {}",
-                    args.host,
+                    host,
                     config::Config::get_config_location()
                 ).red()
             );
@@ -204,3 +488,65 @@ fn resolve_token(args: &Args) -> String {
         }
     }
 }
+
+/// Save a token to the config for the selected host, encrypting it if the
+/// config asks for encrypted tokens
+fn save_token(args: &Args, config: &mut config::Config, token: &str) {
+    let host = args.host();
+    let stored = if config.encrypt_tokens {
+        match secrets::passphrase(false).and_then(|passphrase| secrets::encrypt(token, &passphrase))
+        {
+            Ok(encrypted) => encrypted,
+            Err(e) => {
+                eprintln!("⚠️  Warning: Failed to save token to config: {}", e);
+                return;
+            }
+        }
+    } else {
+        token.to_string()
+    };
+    config.set_token(host, &stored);
+
+    // Save updated config
+    if let Err(e) = config.save() {
+        eprintln!("⚠️  Warning: Failed to save token to config: {}", e);
+    } else if args.verbose {
+        eprintln!(
+            "💾 Token saved to config for host '{}' at: {}",
+            host,
+            config::Config::get_config_location()
+        );
+    }
+}
+
+/// Run a query from the query library, with its default variables
+fn run_saved_query(
+    name: &str,
+    connection: &api::Connection,
+    args: &Args,
+    variables: &vars::Variables,
+) {
+    match queries::load(name).and_then(|query| Ok((query.variables(variables)?, query))) {
+        Ok((variables, query)) => stdio::process_script(&query.query, connection, args, &variables),
+        Err(e) => {
+            eprintln!("{}", format!("❌ {}", e).red());
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Load the variables given with --var and --vars-file, exiting on error
+fn load_variables(args: &Args) -> vars::Variables {
+    vars::Variables::from_args(args).unwrap_or_else(|e| {
+        eprintln!("{}", format!("❌ {}", e).red());
+        std::process::exit(1);
+    })
+}
+
+/// Get the plaintext of a stored token, exiting if it can't be decrypted
+fn reveal_token(token: &str) -> String {
+    secrets::reveal(token).unwrap_or_else(|e| {
+        eprintln!("{}", format!("❌ {}", e).red());
+        std::process::exit(1);
+    })
+}
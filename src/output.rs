@@ -0,0 +1,963 @@
+//! Rendering of QUERY results
+//!
+//! Query results are arrays of (often deeply nested) OCSF events. Apart from
+//! the JSON output, every format here works on a flattened view of each
+//! event, where nested objects become dotted column paths such as
+//! `actor.process.file.name`.
+
+use crate::api::QueryResponse;
+use crate::columnar;
+use clap::ValueEnum;
+use colored::Colorize;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value, json};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+
+/// Widest a column may be when fitting a table to the terminal
+const MAX_COLUMN_WIDTH: usize = 48;
+
+/// Narrowest a column will be squeezed to before columns are dropped
+const MIN_COLUMN_WIDTH: usize = 8;
+
+/// Separator placed between table columns
+const COLUMN_SEPARATOR: &str = " │ ";
+
+/// Supported formats for QUERY results
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    /// Pretty printed JSON array
+    Json,
+    /// Aligned columns sized to the terminal
+    Table,
+    /// Comma separated values with a header row
+    Csv,
+    /// Tab separated values with a header row
+    Tsv,
+    /// One compact JSON object per line
+    Ndjson,
+    /// Apache Parquet file with an inferred schema
+    Parquet,
+    /// Apache Arrow IPC file with an inferred schema
+    Arrow,
+}
+
+impl OutputFormat {
+    /// Whether this format produces binary data that shouldn't go to a terminal
+    pub fn is_binary(self) -> bool {
+        matches!(self, Self::Parquet | Self::Arrow)
+    }
+
+    /// Whether output in this format can be appended to an existing file
+    pub fn can_append(self) -> bool {
+        matches!(self, Self::Table | Self::Csv | Self::Tsv | Self::Ndjson)
+    }
+
+    /// Field delimiter for the delimited formats
+    fn delimiter(self) -> u8 {
+        match self {
+            Self::Tsv => b'\t',
+            _ => b',',
+        }
+    }
+}
+
+/// What to do when the output file already exists
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum ExistingFile {
+    /// Replace the existing file
+    #[default]
+    Overwrite,
+    /// Add the results to the end of the existing file
+    Append,
+    /// Refuse to write and report an error
+    Fail,
+}
+
+impl std::fmt::Display for OutputFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.to_possible_value() {
+            Some(value) => f.write_str(value.get_name()),
+            None => write!(f, "{:?}", self),
+        }
+    }
+}
+
+/// Settings that control how QUERY results are written
+#[derive(Debug, Clone)]
+pub struct OutputOptions {
+    pub format: OutputFormat,
+    /// Restricts the columns of the flattened formats (all when `None`)
+    pub columns: Option<Vec<String>>,
+    /// Wrap each NDJSON row with the search and trace IDs of the query
+    pub with_metadata: bool,
+    /// Write results to this file rather than stdout
+    pub out_file: Option<PathBuf>,
+    /// What to do when `out_file` already exists
+    pub if_exists: ExistingFile,
+}
+
+impl OutputOptions {
+    /// Build options from the command line, using `default_format` when no
+    /// format was given
+    pub fn from_args(args: &crate::Args, default_format: OutputFormat) -> Self {
+        Self {
+            format: args.output.unwrap_or(default_format),
+            columns: args.columns.clone(),
+            with_metadata: args.with_metadata,
+            out_file: args.out_file.clone(),
+            if_exists: args.if_exists,
+        }
+    }
+}
+
+/// Write query results in the selected format
+///
+/// `max_width` is the width that tables are fitted to and `color` whether
+/// they may be styled (see [`render_table`]).
+pub fn write_results(
+    writer: &mut dyn Write,
+    data: &QueryResponse,
+    options: &OutputOptions,
+    max_width: Option<usize>,
+    color: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let selection = options.columns.as_deref();
+    match options.format {
+        OutputFormat::Json => {
+            serde_json::to_writer_pretty(&mut *writer, &data.results)?;
+            writeln!(writer)?;
+        }
+        OutputFormat::Table => write!(
+            writer,
+            "{}",
+            render_table(&data.results, selection, max_width, color)
+        )?,
+        OutputFormat::Csv | OutputFormat::Tsv => write!(
+            writer,
+            "{}",
+            render_delimited(&data.results, selection, options.format.delimiter())?
+        )?,
+        OutputFormat::Ndjson => write_ndjson(writer, data, options.with_metadata)?,
+        OutputFormat::Parquet => {
+            writer.write_all(&columnar::to_parquet(&selected(data, selection))?)?
+        }
+        OutputFormat::Arrow => {
+            writer.write_all(&columnar::to_arrow_ipc(&selected(data, selection))?)?
+        }
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Write query results to stdout
+///
+/// Binary formats are refused when stdout is a terminal.
+pub fn write_stdout(
+    data: &QueryResponse,
+    options: &OutputOptions,
+    max_width: Option<usize>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if options.format.is_binary() && io::stdout().is_terminal() {
+        return Err(format!(
+            "{} output is binary - write it to a file with --out-file or redirect stdout",
+            options.format
+        )
+        .into());
+    }
+    let mut stdout = io::BufWriter::new(io::stdout().lock());
+    write_results(&mut stdout, data, options, max_width, true)
+}
+
+/// Render query results as text, such as for a pager
+///
+/// Binary formats can't be shown as text, so they're an error.
+pub fn render_text(
+    data: &QueryResponse,
+    options: &OutputOptions,
+    max_width: Option<usize>,
+) -> Result<String, Box<dyn std::error::Error>> {
+    if options.format.is_binary() {
+        return Err(format!(
+            "{} output is binary - write it to a file with \\o or --out-file",
+            options.format
+        )
+        .into());
+    }
+    let mut buffer = Vec::new();
+    write_results(&mut buffer, data, options, max_width, true)?;
+    Ok(String::from_utf8(buffer)?)
+}
+
+/// What happened when results were written to a file
+#[derive(Debug)]
+pub struct FileSummary {
+    pub path: PathBuf,
+    pub rows: usize,
+    pub appended: bool,
+}
+
+impl std::fmt::Display for FileSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let verb = if self.appended { "Appended" } else { "Wrote" };
+        let noun = if self.rows == 1 { "row" } else { "rows" };
+        write!(
+            f,
+            "{} {} {} to {}",
+            verb,
+            self.rows,
+            noun,
+            self.path.display()
+        )
+    }
+}
+
+/// Write query results to a file in the selected format
+///
+/// The results are written to a temporary file in the same directory which
+/// then replaces the destination, so readers never see a partial file. What
+/// happens when the destination already exists is decided by
+/// [`OutputOptions::if_exists`].
+pub fn write_results_to_file(
+    path: &Path,
+    data: &QueryResponse,
+    options: &OutputOptions,
+) -> Result<FileSummary, Box<dyn std::error::Error>> {
+    let existing = fs::metadata(path)
+        .ok()
+        .filter(|metadata| metadata.len() > 0);
+    let append = match (options.if_exists, &existing) {
+        (_, None) | (ExistingFile::Overwrite, _) => false,
+        (ExistingFile::Fail, Some(_)) => {
+            return Err(format!(
+                "{} already exists (use --if-exists overwrite or append)",
+                path.display()
+            )
+            .into());
+        }
+        (ExistingFile::Append, Some(_)) => true,
+    };
+    if append && !options.format.can_append() {
+        return Err(format!(
+            "{} output can't be appended to an existing file",
+            options.format
+        )
+        .into());
+    }
+
+    let directory = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let mut temp = tempfile::NamedTempFile::new_in(directory)?;
+    if let Some(metadata) = &existing {
+        // Keep the permissions of the file being replaced
+        fs::set_permissions(temp.path(), metadata.permissions())?;
+    }
+
+    {
+        let mut writer = io::BufWriter::new(temp.as_file_mut());
+        if append {
+            let previous = fs::read(path)?;
+            writer.write_all(&previous)?;
+            if !previous.ends_with(b"\n") {
+                writeln!(writer)?;
+            }
+            match options.format {
+                OutputFormat::Csv | OutputFormat::Tsv => {
+                    append_delimited(&mut writer, &previous, data, options)?
+                }
+                _ => write_results(&mut writer, data, options, None, false)?,
+            }
+        } else {
+            write_results(&mut writer, data, options, None, false)?;
+        }
+        writer.flush()?;
+    }
+    temp.as_file().sync_all()?;
+    temp.persist(path)?;
+
+    Ok(FileSummary {
+        path: path.to_path_buf(),
+        rows: data.results.len(),
+        appended: append,
+    })
+}
+
+/// Append rows to existing delimited output, following its header
+///
+/// Columns that aren't in the existing header are left out, with a warning.
+fn append_delimited(
+    writer: &mut dyn Write,
+    previous: &[u8],
+    data: &QueryResponse,
+    options: &OutputOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let delimiter = options.format.delimiter();
+    let headers: Vec<String> = csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .from_reader(previous)
+        .headers()?
+        .iter()
+        .map(str::to_string)
+        .collect();
+
+    let rows: Vec<Vec<(String, &Value)>> = data.results.iter().map(flatten).collect();
+    let dropped = columns(&rows)
+        .into_iter()
+        .filter(|column| !headers.contains(column))
+        .count();
+    if dropped > 0 {
+        eprintln!(
+            "⚠️  {} column(s) not in the existing header were left out",
+            dropped
+        );
+    }
+
+    write!(
+        writer,
+        "{}",
+        write_delimited(&rows, &headers, delimiter, false)?
+    )?;
+    Ok(())
+}
+
+/// Results restricted to the selected columns, keeping their nesting
+fn selected<'a>(data: &'a QueryResponse, selection: Option<&[String]>) -> Cow<'a, [Value]> {
+    match selection {
+        Some(selection) => Cow::Owned(
+            data.results
+                .iter()
+                .map(|value| project(value, selection))
+                .collect(),
+        ),
+        None => Cow::Borrowed(&data.results),
+    }
+}
+
+/// Keep only the selected columns of a result, rebuilding nested objects
+///
+/// For example selecting `actor.process.pid` from an event yields
+/// `{"actor": {"process": {"pid": ...}}}`.
+pub fn project(value: &Value, selection: &[String]) -> Value {
+    let pairs = flatten(value);
+    let paths: Vec<String> = pairs.iter().map(|(path, _)| path.clone()).collect();
+    let selected = select_columns(&paths, selection);
+    let mut projected = Map::new();
+    for (path, leaf) in selected.iter().zip(row_values(&pairs, &selected)) {
+        let Some(leaf) = leaf else {
+            continue;
+        };
+        // A key with dots in it, such as "a.b" beside a scalar "a", can't be
+        // nested under it, so it's kept as it was
+        let clashes = selected.iter().any(|other| {
+            path.strip_prefix(other.as_str())
+                .is_some_and(|rest| rest.starts_with('.'))
+        });
+        if clashes {
+            projected.insert(path.clone(), (*leaf).clone());
+        } else {
+            let segments: Vec<&str> = path.split('.').collect();
+            insert_nested(&mut projected, &segments, (*leaf).clone());
+        }
+    }
+    Value::Object(projected)
+}
+
+/// Put a value at a nested path, creating objects along the way
+fn insert_nested(map: &mut Map<String, Value>, segments: &[&str], value: Value) {
+    match segments {
+        [] => {}
+        [last] => {
+            map.insert(last.to_string(), value);
+        }
+        [first, rest @ ..] => {
+            if let Value::Object(inner) = map
+                .entry(first.to_string())
+                .or_insert_with(|| Value::Object(Map::new()))
+            {
+                insert_nested(inner, rest, value);
+            }
+        }
+    }
+}
+
+/// Write each result as a single line of compact JSON
+///
+/// With `with_metadata`, each line is an object holding the `search_id` and
+/// `trace_id` of the query alongside the event under `result`.
+pub fn write_ndjson(
+    writer: &mut dyn Write,
+    data: &QueryResponse,
+    with_metadata: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    for result in &data.results {
+        if with_metadata {
+            let row = json!({
+                "search_id": data.search_id,
+                "trace_id": data.trace_id,
+                "result": result,
+            });
+            serde_json::to_writer(&mut *writer, &row)?;
+        } else {
+            serde_json::to_writer(&mut *writer, result)?;
+        }
+        writeln!(writer)?;
+    }
+    Ok(())
+}
+
+/// Flatten a JSON object into `(dotted.path, value)` pairs
+///
+/// Nested objects are descended into; arrays and scalars are leaves. A value
+/// which isn't an object is returned as a single pair with an empty path.
+pub fn flatten(value: &Value) -> Vec<(String, &Value)> {
+    let mut pairs = Vec::new();
+    match value {
+        Value::Object(map) => flatten_into(map, "", &mut pairs),
+        _ => pairs.push((String::new(), value)),
+    }
+    pairs
+}
+
+/// Look up the values of a flattened row in the order of the headers
+///
+/// Headers the row doesn't have are `None`. The row is indexed once rather
+/// than searched for every header, which matters for wide results.
+pub fn row_values<'a>(row: &[(String, &'a Value)], headers: &[String]) -> Vec<Option<&'a Value>> {
+    let mut index: HashMap<&str, &'a Value> = HashMap::with_capacity(row.len());
+    for (path, value) in row {
+        index.entry(path.as_str()).or_insert(*value);
+    }
+    headers
+        .iter()
+        .map(|header| index.get(header.as_str()).copied())
+        .collect()
+}
+
+fn flatten_into<'a>(
+    map: &'a Map<String, Value>,
+    prefix: &str,
+    pairs: &mut Vec<(String, &'a Value)>,
+) {
+    for (key, value) in map {
+        let path = if prefix.is_empty() {
+            key.clone()
+        } else {
+            format!("{}.{}", prefix, key)
+        };
+        match value {
+            Value::Object(child) if !child.is_empty() => flatten_into(child, &path, pairs),
+            _ => pairs.push((path, value)),
+        }
+    }
+}
+
+/// Collect the union of flattened column paths across all rows
+///
+/// Columns are returned in the order they are first seen, so the layout of
+/// the first event drives the layout of the output.
+pub fn columns(rows: &[Vec<(String, &Value)>]) -> Vec<String> {
+    let mut seen = std::collections::HashSet::new();
+    let mut columns = Vec::new();
+    for row in rows {
+        for (path, _) in row {
+            if seen.insert(path.as_str()) {
+                columns.push(path.clone());
+            }
+        }
+    }
+    columns
+}
+
+/// Restrict columns to those matching the user's selection
+///
+/// A selection matches a column exactly or any column nested beneath it, so
+/// `actor.process` selects `actor.process.pid`, `actor.process.file.name`, etc.
+/// Columns are returned in the order of the selection.
+pub fn select_columns(columns: &[String], selection: &[String]) -> Vec<String> {
+    let mut selected: Vec<String> = Vec::new();
+    for wanted in selection {
+        let nested = format!("{}.", wanted);
+        for column in columns {
+            if (column == wanted || column.starts_with(&nested)) && !selected.contains(column) {
+                selected.push(column.clone());
+            }
+        }
+    }
+    selected
+}
+
+/// Render a single value as the text of a table cell
+pub fn cell_text(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        _ => value.to_string(),
+    }
+}
+
+/// Render query results as a table
+///
+/// When `max_width` is given, columns are shrunk (and if necessary dropped
+/// from the right) so that every line fits; long cells are truncated with an
+/// ellipsis.
+///
+/// With `color`, the header is bold and notes are dimmed whenever
+/// [`colored`] is styling output; without it the table is plain text, as
+/// it must be when written to a file.
+pub fn render_table(
+    results: &[Value],
+    selection: Option<&[String]>,
+    max_width: Option<usize>,
+    color: bool,
+) -> String {
+    let rows: Vec<Vec<(String, &Value)>> = results.iter().map(flatten).collect();
+    let mut headers = columns(&rows);
+    if let Some(selection) = selection {
+        headers = select_columns(&headers, selection);
+    }
+    if headers.is_empty() {
+        return String::new();
+    }
+
+    let cells: Vec<Vec<String>> = rows
+        .iter()
+        .map(|row| {
+            row_values(row, &headers)
+                .into_iter()
+                .map(|value| {
+                    value
+                        .map(|value| single_line(&cell_text(value)))
+                        .unwrap_or_default()
+                })
+                .collect()
+        })
+        .collect();
+
+    let mut widths: Vec<usize> = headers
+        .iter()
+        .enumerate()
+        .map(|(i, header)| {
+            cells
+                .iter()
+                .map(|row| row[i].chars().count())
+                .chain(std::iter::once(header.chars().count()))
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+
+    let mut hidden = 0;
+    if let Some(max_width) = max_width {
+        hidden = fit_widths(&mut widths, max_width);
+    }
+    let visible = widths.len();
+
+    let mut output = String::new();
+    let header_line: Vec<String> = headers[..visible]
+        .iter()
+        .zip(&widths)
+        .map(|(header, width)| pad(&truncate_start(header, *width), *width))
+        .collect();
+    let header_line = header_line.join(COLUMN_SEPARATOR);
+    if color {
+        output.push_str(&header_line.bold().to_string());
+    } else {
+        output.push_str(&header_line);
+    }
+    output.push('\n');
+
+    let rule: Vec<String> = widths.iter().map(|width| "─".repeat(*width)).collect();
+    output.push_str(&rule.join("─┼─"));
+    output.push('\n');
+
+    for row in &cells {
+        let line: Vec<String> = row[..visible]
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| pad(&truncate(cell, *width), *width))
+            .collect();
+        output.push_str(line.join(COLUMN_SEPARATOR).trim_end());
+        output.push('\n');
+    }
+
+    if hidden > 0 {
+        let noun = if hidden == 1 { "column" } else { "columns" };
+        let note = format!(
+            "({} more {} not shown - select columns to see them)",
+            hidden, noun
+        );
+        if color {
+            output.push_str(&format!("{}\n", note.dimmed()));
+        } else {
+            output.push_str(&format!("{}\n", note));
+        }
+    }
+
+    output
+}
+
+/// Render query results as delimiter separated values with a header row
+///
+/// The header is the union of columns across all rows, so rows missing a
+/// column get an empty cell. Fields are quoted when they contain the
+/// delimiter, quotes or line breaks.
+pub fn render_delimited(
+    results: &[Value],
+    selection: Option<&[String]>,
+    delimiter: u8,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let rows: Vec<Vec<(String, &Value)>> = results.iter().map(flatten).collect();
+    let mut headers = columns(&rows);
+    if let Some(selection) = selection {
+        headers = select_columns(&headers, selection);
+    }
+    write_delimited(&rows, &headers, delimiter, true)
+}
+
+/// Render flattened rows under the given headers
+fn write_delimited(
+    rows: &[Vec<(String, &Value)>],
+    headers: &[String],
+    delimiter: u8,
+    include_header: bool,
+) -> Result<String, Box<dyn std::error::Error>> {
+    if headers.is_empty() {
+        return Ok(String::new());
+    }
+
+    let mut writer = csv::WriterBuilder::new()
+        .delimiter(delimiter)
+        .from_writer(Vec::new());
+    if include_header {
+        writer.write_record(headers)?;
+    }
+    for row in rows {
+        writer.write_record(
+            row_values(row, headers)
+                .into_iter()
+                .map(|value| value.map(cell_text).unwrap_or_default()),
+        )?;
+    }
+
+    let bytes = writer.into_inner().map_err(|e| e.into_error())?;
+    Ok(String::from_utf8(bytes)?)
+}
+
+/// Shrink column widths to fit in `max_width`, returning how many columns
+/// had to be dropped from the right
+fn fit_widths(widths: &mut Vec<usize>, max_width: usize) -> usize {
+    for width in widths.iter_mut() {
+        *width = (*width).min(MAX_COLUMN_WIDTH);
+    }
+
+    let total = |widths: &[usize]| {
+        widths.iter().sum::<usize>()
+            + COLUMN_SEPARATOR.chars().count() * widths.len().saturating_sub(1)
+    };
+
+    // Take a character at a time from the widest column until everything fits
+    while total(widths) > max_width {
+        let (widest, width) = widths
+            .iter()
+            .copied()
+            .enumerate()
+            .max_by_key(|(_, width)| *width)
+            .unwrap_or((0, 0));
+        if width <= MIN_COLUMN_WIDTH {
+            break;
+        }
+        widths[widest] -= 1;
+    }
+
+    // Still too wide, so drop columns from the right (but always show one)
+    let mut hidden = 0;
+    while widths.len() > 1 && total(widths) > max_width {
+        widths.pop();
+        hidden += 1;
+    }
+    hidden
+}
+
+/// Replace line breaks and tabs so a cell can't break the table layout
+pub fn single_line(text: &str) -> String {
+    text.replace(['\n', '\r', '\t'], " ")
+}
+
+/// Truncate text to `width` characters, marking truncation with an ellipsis
+pub fn truncate(text: &str, width: usize) -> String {
+    if text.chars().count() <= width {
+        text.to_string()
+    } else if width == 0 {
+        String::new()
+    } else {
+        let mut truncated: String = text.chars().take(width - 1).collect();
+        truncated.push('…');
+        truncated
+    }
+}
+
+/// Truncate text to `width` characters by dropping the start
+///
+/// Used for column headers, where the end of a dotted path is the most
+/// descriptive part.
+pub fn truncate_start(text: &str, width: usize) -> String {
+    let count = text.chars().count();
+    if count <= width {
+        text.to_string()
+    } else if width == 0 {
+        String::new()
+    } else {
+        let mut truncated = String::from('…');
+        truncated.extend(text.chars().skip(count - width + 1));
+        truncated
+    }
+}
+
+/// Pad text with spaces to `width` characters
+pub fn pad(text: &str, width: usize) -> String {
+    format!("{:<width$}", text, width = width)
+}
+
+/// Width of the terminal attached to stdout, if there is one
+pub fn terminal_width() -> Option<usize> {
+    terminal_size::terminal_size().map(|(terminal_size::Width(width), _)| width as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flatten_nested_objects() {
+        let event = json!({
+            "time": 1,
+            "actor": {"process": {"pid": 42, "file": {"name": "regsvr32.exe"}}},
+            "tags": ["a", "b"],
+            "empty": {}
+        });
+        let paths: Vec<String> = flatten(&event).into_iter().map(|(path, _)| path).collect();
+        assert_eq!(
+            paths,
+            vec![
+                "time",
+                "actor.process.pid",
+                "actor.process.file.name",
+                "tags",
+                "empty"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_columns_union_in_first_seen_order() {
+        let results = [json!({"a": 1, "b": 2}), json!({"c": 3, "a": 4})];
+        let rows: Vec<_> = results.iter().map(flatten).collect();
+        assert_eq!(columns(&rows), vec!["a", "b", "c"]);
+    }
+
+    #[test]
+    fn test_select_columns_by_prefix() {
+        let columns: Vec<String> = [
+            "time",
+            "actor.process.pid",
+            "actor.process.file.name",
+            "actor.user",
+        ]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+        let selection = vec!["actor.process".to_string(), "time".to_string()];
+        assert_eq!(
+            select_columns(&columns, &selection),
+            vec!["actor.process.pid", "actor.process.file.name", "time"]
+        );
+    }
+
+    #[test]
+    fn test_render_table_fits_width() {
+        let results = [
+            json!({"name": "a".repeat(100), "pid": 1, "host": "web-01"}),
+            json!({"name": "short", "pid": 22}),
+        ];
+        let table = render_table(&results, None, Some(40), false);
+        for line in table.lines() {
+            assert!(line.chars().count() <= 40, "line too long: {line}");
+        }
+        assert!(table.contains('…'));
+        assert!(table.lines().next().unwrap().starts_with("name"));
+    }
+
+    #[test]
+    fn test_render_table_drops_columns_when_too_narrow() {
+        let results = [json!({"a": "x".repeat(20), "b": "y".repeat(20), "c": "z".repeat(20)})];
+        let table = render_table(&results, None, Some(12), false);
+        assert!(table.contains("2 more columns not shown"));
+    }
+
+    #[test]
+    fn test_render_csv_union_of_columns() {
+        let results = [
+            json!({"actor": {"process": {"file": {"name": "a.exe"}}}, "pid": 1}),
+            json!({"pid": 2, "message": "said \"hi\", then left"}),
+        ];
+        let csv = render_delimited(&results, None, b',').unwrap();
+        assert_eq!(
+            csv,
+            "actor.process.file.name,pid,message\n\
+             a.exe,1,\n\
+             ,2,\"said \"\"hi\"\", then left\"\n"
+        );
+    }
+
+    #[test]
+    fn test_render_tsv_quotes_tabs_and_newlines() {
+        let results = [json!({"a": "one\ttwo", "b": "line\nbreak", "c": ["x", "y"]})];
+        let tsv = render_delimited(&results, None, b'\t').unwrap();
+        assert_eq!(
+            tsv,
+            "a\tb\tc\n\"one\ttwo\"\t\"line\nbreak\"\t\"[\"\"x\"\",\"\"y\"\"]\"\n"
+        );
+    }
+
+    #[test]
+    fn test_project_keeps_nesting() {
+        let event =
+            json!({"time": 1, "actor": {"process": {"pid": 42, "name": "x"}, "user": "bob"}});
+        let selection = vec!["actor.process.pid".to_string(), "time".to_string()];
+        assert_eq!(
+            project(&event, &selection),
+            json!({"actor": {"process": {"pid": 42}}, "time": 1})
+        );
+    }
+
+    #[test]
+    fn test_row_values_in_header_order() {
+        let event = json!({"a": 1, "b": {"c": "x"}});
+        let row = flatten(&event);
+        let headers = vec!["b.c".to_string(), "missing".to_string(), "a".to_string()];
+        assert_eq!(
+            row_values(&row, &headers),
+            vec![Some(&json!("x")), None, Some(&json!(1))]
+        );
+    }
+
+    #[test]
+    fn test_project_keeps_dotted_keys() {
+        // "a.b" flattens to the same path as a nested b, but a is a scalar
+        let event = json!({"a": 1, "a.b": 2, "c": 3});
+        let selection = vec!["a".to_string()];
+        assert_eq!(project(&event, &selection), json!({"a": 1, "a.b": 2}));
+        let event = json!({"a.b": 2, "a": 1});
+        assert_eq!(project(&event, &selection), json!({"a.b": 2, "a": 1}));
+    }
+
+    fn query_response(results: Vec<Value>) -> QueryResponse {
+        QueryResponse {
+            command: "QUERY".to_string(),
+            search_id: "search-1".to_string(),
+            trace_id: "trace-1".to_string(),
+            results,
+        }
+    }
+
+    fn file_options(format: OutputFormat, if_exists: ExistingFile) -> OutputOptions {
+        OutputOptions {
+            format,
+            columns: None,
+            with_metadata: false,
+            out_file: None,
+            if_exists,
+        }
+    }
+
+    #[test]
+    fn test_write_file_policies() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("results.ndjson");
+        let data = query_response(vec![json!({"a": 1})]);
+
+        let options = file_options(OutputFormat::Ndjson, ExistingFile::Fail);
+        let summary = write_results_to_file(&path, &data, &options).unwrap();
+        assert_eq!(
+            summary.to_string(),
+            format!("Wrote 1 row to {}", path.display())
+        );
+        assert!(write_results_to_file(&path, &data, &options).is_err());
+
+        let options = file_options(OutputFormat::Ndjson, ExistingFile::Append);
+        let summary = write_results_to_file(&path, &data, &options).unwrap();
+        assert!(summary.appended);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "{\"a\":1}\n{\"a\":1}\n");
+
+        let options = file_options(OutputFormat::Ndjson, ExistingFile::Overwrite);
+        write_results_to_file(&path, &data, &options).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "{\"a\":1}\n");
+
+        // Only the destination should be left behind
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_write_table_file_is_plain_text() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("results.txt");
+        let data = query_response(vec![json!({"a": 1})]);
+
+        // Tables written to a file are never styled, even from a terminal
+        let options = file_options(OutputFormat::Table, ExistingFile::Append);
+        write_results_to_file(&path, &data, &options).unwrap();
+        write_results_to_file(&path, &data, &options).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "a\n─\n1\na\n─\n1\n");
+    }
+
+    #[test]
+    fn test_append_csv_follows_existing_header() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("results.csv");
+        fs::write(&path, "b,a\n1,2\n").unwrap();
+
+        let data = query_response(vec![json!({"a": 3, "b": 4, "c": 5})]);
+        let options = file_options(OutputFormat::Csv, ExistingFile::Append);
+        write_results_to_file(&path, &data, &options).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "b,a\n1,2\n4,3\n");
+
+        let options = file_options(OutputFormat::Parquet, ExistingFile::Append);
+        assert!(write_results_to_file(&path, &data, &options).is_err());
+    }
+
+    #[test]
+    fn test_write_ndjson() {
+        let data = query_response(vec![json!({"a": {"b": 1}}), json!({"c": "two"})]);
+
+        let mut plain = Vec::new();
+        write_ndjson(&mut plain, &data, false).unwrap();
+        assert_eq!(
+            String::from_utf8(plain).unwrap(),
+            "{\"a\":{\"b\":1}}\n{\"c\":\"two\"}\n"
+        );
+
+        let mut wrapped = Vec::new();
+        write_ndjson(&mut wrapped, &data, true).unwrap();
+        let wrapped = String::from_utf8(wrapped).unwrap();
+        let first: Value = serde_json::from_str(wrapped.lines().next().unwrap()).unwrap();
+        assert_eq!(
+            first,
+            json!({"search_id": "search-1", "trace_id": "trace-1", "result": {"a": {"b": 1}}})
+        );
+        assert_eq!(wrapped.lines().count(), 2);
+    }
+
+    #[test]
+    fn test_truncate() {
+        assert_eq!(truncate("hello", 10), "hello");
+        assert_eq!(truncate("hello world", 6), "hello…");
+        assert_eq!(truncate("hello", 0), "");
+        assert_eq!(truncate_start("actor.process.pid", 8), "…ess.pid");
+    }
+}
@@ -0,0 +1,113 @@
+//! Showing long REPL output through a pager
+//!
+//! Output is sent to `$PAGER`, or `less -R` when it isn't set, so that ANSI
+//! colours survive. Setting `PAGER` to an empty string turns paging off. If
+//! the pager can't be run, the output is printed as usual.
+
+use crate::interrupt;
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::io::{self, IsTerminal, Write};
+use std::process::{Command, Stdio};
+
+/// The pager to use when `$PAGER` isn't set
+#[cfg(windows)]
+const DEFAULT_PAGER: &str = "more";
+#[cfg(not(windows))]
+const DEFAULT_PAGER: &str = "less -R";
+
+/// Lines kept free below paged output for the prompt
+const PROMPT_LINES: usize = 2;
+
+/// When output goes through the pager
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PagerMode {
+    /// Always, when writing to a terminal
+    On,
+    /// Never
+    Off,
+    /// When the output is taller than the terminal
+    #[default]
+    Auto,
+}
+
+impl std::fmt::Display for PagerMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.to_possible_value() {
+            Some(value) => f.write_str(value.get_name()),
+            None => write!(f, "{:?}", self),
+        }
+    }
+}
+
+/// Get the pager command from `$PAGER`, split on whitespace
+///
+/// Returns `None` if `PAGER` is set but empty.
+fn pager_command() -> Option<Vec<String>> {
+    let pager = std::env::var("PAGER").unwrap_or_else(|_| DEFAULT_PAGER.to_string());
+    let command: Vec<String> = pager.split_whitespace().map(str::to_string).collect();
+    (!command.is_empty()).then_some(command)
+}
+
+/// Whether text of this many lines should be paged
+fn should_page(mode: PagerMode, lines: usize, height: Option<usize>) -> bool {
+    match mode {
+        PagerMode::On => true,
+        PagerMode::Off => false,
+        PagerMode::Auto => height.is_some_and(|height| lines + PROMPT_LINES > height),
+    }
+}
+
+/// Send text to the pager and wait for the user to quit it
+///
+/// Ctrl+C is left to the pager while it runs rather than ending fsqlctl too.
+fn run_pager(command: &[String], text: &str) -> io::Result<()> {
+    let _handler = interrupt::Handler::install();
+    let mut child = Command::new(&command[0])
+        .args(&command[1..])
+        .stdin(Stdio::piped())
+        .spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        // The pager closes its input if it's quit before reading everything
+        match stdin.write_all(text.as_bytes()) {
+            Err(e) if e.kind() != io::ErrorKind::BrokenPipe => return Err(e),
+            _ => {}
+        }
+    }
+    child.wait()?;
+    Ok(())
+}
+
+/// Print text, through the pager if the mode calls for it
+///
+/// Nothing is paged unless stdout is a terminal.
+pub fn page(text: &str, mode: PagerMode) {
+    let height = terminal_size::terminal_size().map(|(_, terminal_size::Height(h))| h as usize);
+    let command = pager_command();
+    if let Some(command) = command
+        && io::stdout().is_terminal()
+        && should_page(mode, text.lines().count(), height)
+    {
+        match run_pager(&command, text) {
+            Ok(()) => return,
+            Err(e) => eprintln!("⚠️  Failed to run pager '{}': {}", command[0], e),
+        }
+    }
+    print!("{}", text);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_should_page() {
+        assert!(should_page(PagerMode::On, 1, Some(40)));
+        assert!(!should_page(PagerMode::Off, 1000, Some(40)));
+        assert!(should_page(PagerMode::Auto, 39, Some(40)));
+        assert!(!should_page(PagerMode::Auto, 10, Some(40)));
+        // Without a terminal size there's nothing to compare with
+        assert!(!should_page(PagerMode::Auto, 1000, None));
+    }
+}
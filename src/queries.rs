@@ -0,0 +1,239 @@
+//! A library of named queries saved as files
+//!
+//! Each query is a TOML file under `<config dir>/queries/`, so a team can
+//! share a directory of hunts through git. Queries in subdirectories are
+//! named by their path, e.g. `persistence/run-keys` for
+//! `queries/persistence/run-keys.toml`:
+//!
+//! ```toml
+//! description = "Processes loading a DLL with regsvr32"
+//! tags = ["execution", "lolbin"]
+//! query = """
+//! QUERY module_activity.**
+//!   WITH module_activity.actor.process.file.name = :file
+//!   AFTER :window"""
+//!
+//! [vars]
+//! file = "regsvr32.exe"
+//! window = "24h"
+//! ```
+//!
+//! The `vars` are defaults for the query's variables; values given with
+//! `--var`, `--vars-file` or `\set` take precedence.
+
+use crate::vars::Variables;
+use colored::Colorize;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Directory under the config directory that holds saved queries
+const DIRECTORY: &str = "queries";
+
+/// File extension of saved queries
+const EXTENSION: &str = "toml";
+
+/// A saved query and what's known about it
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SavedQuery {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+
+    /// The FSQL, which may hold several statements
+    pub query: String,
+
+    /// Default values for the query's variables
+    #[serde(default, skip_serializing_if = "toml::Table::is_empty")]
+    pub vars: toml::Table,
+}
+
+impl SavedQuery {
+    /// The query's default variables, overridden by those given
+    pub fn variables(&self, overrides: &Variables) -> Result<Variables, String> {
+        let mut variables = Variables::from_table(self.vars.clone())?;
+        variables.extend(overrides.clone());
+        Ok(variables)
+    }
+}
+
+/// The directory saved queries are kept in
+pub fn directory() -> PathBuf {
+    crate::config::get_config_dir().join(DIRECTORY)
+}
+
+/// Check a query name, which may include `/` separated subdirectories
+fn check_name(name: &str) -> Result<(), String> {
+    let valid = name.split('/').all(|segment| {
+        !segment.is_empty()
+            && segment
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-'))
+    });
+    if valid {
+        Ok(())
+    } else {
+        Err(format!(
+            "Invalid query name '{}': use letters, digits, '-' and '_', with '/' between directories",
+            name
+        ))
+    }
+}
+
+/// The file a query is saved in
+fn path_in(directory: &Path, name: &str) -> Result<PathBuf, String> {
+    check_name(name)?;
+    Ok(directory.join(format!("{}.{}", name, EXTENSION)))
+}
+
+fn load_from(directory: &Path, name: &str) -> Result<SavedQuery, String> {
+    let path = path_in(directory, name)?;
+    let contents = fs::read_to_string(&path).map_err(|e| match e.kind() {
+        io::ErrorKind::NotFound => format!("No saved query named '{}'", name),
+        _ => format!("Failed to read {}: {}", path.display(), e),
+    })?;
+    toml::from_str(&contents).map_err(|e| format!("Invalid query in {}: {}", path.display(), e))
+}
+
+fn save_to(directory: &Path, name: &str, query: &SavedQuery) -> Result<PathBuf, String> {
+    let path = path_in(directory, name)?;
+    let contents = toml::to_string_pretty(query).map_err(|e| e.to_string())?;
+    path.parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|_| fs::write(&path, contents))
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    Ok(path)
+}
+
+/// Collect the names of the queries beneath a directory, in a stable order
+fn find_names(directory: &Path, prefix: &str, names: &mut Vec<String>) -> io::Result<()> {
+    let mut entries = fs::read_dir(directory)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<io::Result<Vec<_>>>()?;
+    entries.sort();
+    for path in entries {
+        let Some(stem) = path.file_stem().and_then(|stem| stem.to_str()) else {
+            continue;
+        };
+        if path.is_dir() {
+            find_names(&path, &format!("{}{}/", prefix, stem), names)?;
+        } else if path.extension().is_some_and(|ext| ext == EXTENSION) {
+            names.push(format!("{}{}", prefix, stem));
+        }
+    }
+    Ok(())
+}
+
+fn names_in(directory: &Path) -> Result<Vec<String>, String> {
+    let mut names = Vec::new();
+    match find_names(directory, "", &mut names) {
+        Ok(()) => Ok(names),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(names),
+        Err(e) => Err(format!("Failed to read {}: {}", directory.display(), e)),
+    }
+}
+
+/// Load a saved query by name
+pub fn load(name: &str) -> Result<SavedQuery, String> {
+    load_from(&directory(), name)
+}
+
+/// Save a query under a name, replacing any query of that name
+///
+/// Returns the path of the file it was saved in.
+pub fn save(name: &str, query: &SavedQuery) -> Result<PathBuf, String> {
+    save_to(&directory(), name, query)
+}
+
+/// Print the saved queries, optionally only those with the given tag
+pub fn print_list(tag: Option<&str>) {
+    let directory = directory();
+    let names = match names_in(&directory) {
+        Ok(names) => names,
+        Err(e) => {
+            eprintln!("{}", format!("❌ {}", e).red());
+            return;
+        }
+    };
+
+    let mut shown = 0;
+    for name in names {
+        match load_from(&directory, &name) {
+            Ok(query) => {
+                if tag.is_some_and(|tag| !query.tags.iter().any(|t| t.eq_ignore_ascii_case(tag))) {
+                    continue;
+                }
+                let mut line = format!("  {}", name.cyan());
+                if let Some(description) = &query.description {
+                    line = format!("{} - {}", line, description);
+                }
+                if !query.tags.is_empty() {
+                    line = format!(
+                        "{} {}",
+                        line,
+                        format!("[{}]", query.tags.join(", ")).dimmed()
+                    );
+                }
+                println!("{}", line);
+                shown += 1;
+            }
+            Err(e) => eprintln!("  {}", format!("⚠️  {}", e).yellow()),
+        }
+    }
+    if shown == 0 {
+        match tag {
+            Some(tag) => println!("No saved queries tagged '{}'", tag),
+            None => println!("No saved queries in {}", directory.display()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_save_and_load() {
+        let directory = tempfile::tempdir().unwrap();
+        let query = SavedQuery {
+            description: Some("Run keys".to_string()),
+            tags: vec!["persistence".to_string()],
+            query: "QUERY registry_key_activity.**\n  AFTER :window".to_string(),
+            vars: toml::from_str("window = \"7d\"").unwrap(),
+        };
+        let path = save_to(directory.path(), "persistence/run-keys", &query).unwrap();
+        assert!(path.ends_with("persistence/run-keys.toml"));
+        save_to(directory.path(), "connectors", &SavedQuery::default()).unwrap();
+
+        assert_eq!(
+            load_from(directory.path(), "persistence/run-keys").unwrap(),
+            query
+        );
+        assert_eq!(
+            names_in(directory.path()).unwrap(),
+            vec!["connectors", "persistence/run-keys"]
+        );
+        assert_eq!(
+            load_from(directory.path(), "missing").unwrap_err(),
+            "No saved query named 'missing'"
+        );
+        assert!(save_to(directory.path(), "../escape", &query).is_err());
+        assert!(names_in(&directory.path().join("none")).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_default_variables() {
+        let query = SavedQuery {
+            vars: toml::from_str("window = \"7d\"\nfile = \"a.exe\"").unwrap(),
+            ..SavedQuery::default()
+        };
+        let mut overrides = Variables::default();
+        overrides.set("file", "b.exe").unwrap();
+        let variables = query.variables(&overrides).unwrap();
+        assert_eq!(variables.get("window"), Some("7d"));
+        assert_eq!(variables.get("file"), Some("'b.exe'"));
+    }
+}
@@ -1,324 +1,381 @@
-use crate::{Args, api};
-use clearscreen;
+use crate::Args;
+use crate::api::{self, ApiError, Connection};
+use crate::command::{self, FsqlCommand, Presenter};
+use crate::editor;
+use crate::fsql::{self, lexer::TokenKind};
+use crate::helper::FsqlHelper;
+use crate::jwt;
+use crate::output::{self, OutputFormat, OutputOptions};
+use crate::pager::{self, PagerMode};
+use crate::queries;
+use crate::vars::{self, Variables};
+use crate::viewer;
+use clap::ValueEnum;
 use colored::Colorize;
 use rand::prelude::IndexedRandom;
 use rand::rng;
-use rustyline::DefaultEditor;
+use rustyline::Editor;
 use rustyline::error::ReadlineError;
-use serde_json;
+use rustyline::history::DefaultHistory;
 use std::path::PathBuf;
 
-/// Handle validate command
-fn handle_validate(trimmed_input: &str, api_url: &str, token: &str, args: &Args) {
-    let result = api::dispatch_command(trimmed_input, api_url, token, args.verbose);
-    match result {
-        Ok(response_text) => {
-            // Parse and pretty print JSON response
-            match serde_json::from_str::<api::ValidateResponse>(&response_text) {
-                Ok(data) => {
-                    if args.verbose {
-                        println!("{}", "Command:".cyan());
-                        println!("{}", data.command);
-                        println!();
-                    }
-                    // The invalid query part probably will never display given the current API because
-                    // it doesn't actually return is_valid: false - it gives a different error with an
-                    // error code. We should probably fix the API.
-                    if data.is_valid {
-                        println!("✅ Query is valid")
-                    } else {
-                        eprintln!("❌ Query is invalid");
-                    }
-                }
-                Err(e) => {
-                    if args.verbose {
-                        eprintln!("❌ Failed to parse response as JSON: {}", e);
-                    }
-                    println!("{}", response_text); // Output raw response if not valid JSON
-                }
-            }
-        }
-        Err(e) => {
-            eprintln!("❌ Error dispatching command: {e}");
+/// The line editor used by the REPL
+type ReplEditor = Editor<FsqlHelper, DefaultHistory>;
+
+/// Presents API responses in the interactive REPL
+struct ReplPresenter {
+    verbose: bool,
+    /// How QUERY results are displayed
+    output: OutputOptions,
+    /// When long output goes through the pager
+    pager: PagerMode,
+    /// Open QUERY results in the viewer instead of printing them
+    tui: bool,
+    /// The last QUERY results, for \view
+    last_results: Option<api::QueryResponse>,
+}
+
+impl ReplPresenter {
+    /// Print the command echoed back by the API when in verbose mode
+    fn print_command(&self, command: &str) {
+        if self.verbose {
+            println!("{}", "Command:".cyan());
+            println!("{}", command);
+            println!();
         }
     }
 }
 
-/// Handle explain graphql command
-fn handle_explain_graphql(trimmed_input: &str, api_url: &str, token: &str, args: &Args) {
-    let result = api::dispatch_command(trimmed_input, api_url, token, args.verbose);
-    match result {
-        Ok(response_text) => {
-            // Parse and pretty print JSON response
-            match serde_json::from_str::<api::ExplainGraphqlResponse>(&response_text) {
-                Ok(data) => {
-                    if args.verbose {
-                        println!("{}", "Command:".cyan());
-                        println!("{}", data.command);
-                        println!();
-                    }
-                    println!("{}", "Graphql:".green());
-                    println!("{}", data.query);
-                }
-                Err(e) => {
-                    if args.verbose {
-                        eprintln!("❌ Failed to parse response as JSON: {}", e);
-                    }
-                    println!("{}", response_text); // Output raw response if not valid JSON
-                }
-            }
+impl Presenter for ReplPresenter {
+    fn explain_connectors(&mut self, data: &api::ExplainConnectorsResponse) {
+        self.print_command(&data.command);
+        println!("{}", "Connectors:".cyan());
+        match serde_json::to_string_pretty(&data.connectors) {
+            Ok(pretty_json) => println!("{}", pretty_json),
+            Err(_) => println!("{:?}", data.connectors), // Fallback to debug output
         }
-        Err(e) => {
-            eprintln!("❌ Error dispatching command: {e}");
+        let total = data.connectors.len();
+        if total == 1 {
+            println!("{} connector found", total);
+        } else {
+            println!("{} connectors found", total);
         }
     }
-}
 
-/// Handle explain version command
-fn handle_explain_version(trimmed_input: &str, api_url: &str, token: &str, args: &Args) {
-    let result = api::dispatch_command(trimmed_input, api_url, token, args.verbose);
-    match result {
-        Ok(response_text) => {
-            // Parse and pretty print JSON response
-            match serde_json::from_str::<api::ExplainVersionResponse>(&response_text) {
-                Ok(data) => {
-                    if args.verbose {
-                        println!("{}", "Command:".cyan());
-                        println!("{}", data.command);
-                        println!();
-                    }
-                    println!("fsql: {}", data.fsql);
-                    println!(" qdm: {}", data.qdm);
-                }
-                Err(e) => {
-                    if args.verbose {
-                        eprintln!("❌ Failed to parse response as JSON: {}", e);
-                    }
-                    println!("{}", response_text); // Output raw response if not valid JSON
-                }
-            }
-        }
-        Err(e) => {
-            eprintln!("❌ Error dispatching command: {e}");
+    fn explain_version(&mut self, data: &api::ExplainVersionResponse) {
+        self.print_command(&data.command);
+        println!("fsql: {}", data.fsql);
+        println!(" qdm: {}", data.qdm);
+    }
+
+    fn explain_schema(&mut self, data: &api::ExplainSchemaResponse) {
+        self.print_command(&data.command);
+        let text = match serde_json::to_string_pretty(&data.schema) {
+            Ok(pretty_json) => pretty_json,
+            Err(_) => format!("{:?}", data.schema), // Fallback to debug output
+        };
+        pager::page(&format!("{}\n{}\n", "Schema:".green(), text), self.pager);
+    }
+
+    fn explain_graphql(&mut self, data: &api::ExplainGraphqlResponse) {
+        self.print_command(&data.command);
+        println!("{}", "Graphql:".green());
+        println!("{}", data.query);
+    }
+
+    fn explain_attributes(&mut self, data: &api::ExplainAttributesResponse) {
+        self.print_command(&data.command);
+        println!("{}", "Attributes:".green());
+        for attr in data.attributes.iter() {
+            println!("{attr}");
         }
     }
-}
 
-/// Handle summarize
-fn handle_summarize(trimmed_input: &str, api_url: &str, token: &str, args: &Args) {
-    let result = api::dispatch_command(trimmed_input, api_url, token, args.verbose);
-    match result {
-        Ok(response_text) => {
-            // Parse and pretty print JSON response
-            match serde_json::from_str::<api::SummarizeResponse>(&response_text) {
-                Ok(data) => {
-                    if args.verbose {
-                        println!("{}", "Command:".cyan());
-                        println!("{}", data.command);
-                        println!();
-                    }
-                    println!("Operation: {}", data.operation);
-                    println!("    Value: {}", data.value);
-                }
-                Err(e) => {
-                    if args.verbose {
-                        eprintln!("❌ Failed to parse response as JSON: {}", e);
-                    }
-                    println!("{}", response_text); // Output raw response if not valid JSON
-                }
-            }
+    fn explain(&mut self, data: &api::ExplainResponse) {
+        if self.verbose {
+            println!("{}", "Original Input:".cyan());
+            println!("{}", data.input);
+            println!();
         }
-        Err(e) => {
-            eprintln!("❌ Error dispatching command: {e}");
+        self.print_command(&data.command);
+        println!("{}", "Expanded Query:".green());
+        // If the parsed value is a string, just print it so that the newline characters are
+        // honoured. If not, use the pretty printer from serde_json
+        match &data.expanded_query {
+            serde_json::Value::String(s) => println!("{}", s),
+            _ => match serde_json::to_string_pretty(&data.expanded_query) {
+                Ok(pretty_json) => println!("{}", pretty_json),
+                Err(_) => println!("{}", data.expanded_query), // Fallback to compact JSON
+            },
         }
     }
-}
 
-/// Handle explain connectors command
-fn handle_explain_connectors(trimmed_input: &str, api_url: &str, token: &str, args: &Args) {
-    let result = api::dispatch_command(trimmed_input, api_url, token, args.verbose);
-    match result {
-        Ok(response_text) => {
-            // Parse and pretty print JSON response
-            match serde_json::from_str::<api::ExplainConnectorsResponse>(&response_text) {
-                Ok(data) => {
-                    if args.verbose {
-                        println!("{}", "Command:".cyan());
-                        println!("{}", data.command);
-                        println!();
-                    }
-                    println!("{}", "Connectors:".cyan());
-                    match serde_json::to_string_pretty(&data.connectors) {
-                        Ok(pretty_json) => println!("{}", pretty_json),
-                        Err(_) => println!("{}", response_text), // Fallback to raw text
-                    }
-                    let total = data.connectors.len();
-                    if total == 1 {
-                        println!("{} connector found", data.connectors.len());
-                    } else {
-                        println!("{} connectors found", data.connectors.len());
-                    }
-                }
-                Err(e) => {
-                    if args.verbose {
-                        eprintln!("❌ Failed to parse response as JSON: {}", e);
-                    }
-                    println!("{}", response_text); // Output raw response if not valid JSON
-                }
+    fn summarize(&mut self, data: &api::SummarizeResponse) {
+        self.print_command(&data.command);
+        println!("Operation: {}", data.operation);
+        println!("    Value: {}", data.value);
+    }
+
+    fn validate(&mut self, data: &api::ValidateResponse) {
+        self.print_command(&data.command);
+        // The invalid query part probably will never display given the current API because
+        // it doesn't actually return is_valid: false - it gives a different error with an
+        // error code. We should probably fix the API.
+        if data.is_valid {
+            println!("✅ Query is valid")
+        } else {
+            eprintln!("❌ Query is invalid");
+        }
+    }
+
+    fn query(&mut self, data: &api::QueryResponse) {
+        if self.verbose {
+            println!("{} {}", "Command:".cyan(), data.command);
+            println!();
+            println!("{} {}", "Trace ID:".cyan(), data.trace_id);
+            println!();
+        }
+        println!("{} {}", "Search ID:".cyan(), data.search_id);
+        println!();
+        self.last_results = Some(data.clone());
+        if let Some(path) = &self.output.out_file {
+            match output::write_results_to_file(path, data, &self.output) {
+                Ok(summary) => println!("💾 {}", summary),
+                Err(e) => eprintln!("❌ Failed to write results: {}", e),
             }
+            return;
         }
-        Err(e) => {
-            eprintln!("❌ Error dispatching command: {e}");
+
+        if self.tui {
+            if let Err(e) = viewer::show(data, &self.output) {
+                eprintln!("❌ {}", e);
+            }
+        } else {
+            match output::render_text(data, &self.output, output::terminal_width()) {
+                Ok(text) => pager::page(&format!("{}\n{}", "Results:".green(), text), self.pager),
+                Err(e) => eprintln!("❌ Failed to write results: {}", e),
+            }
         }
+        let total = data.results.len();
+        if total == 1 {
+            println!("{} result found", total);
+        } else {
+            println!("{} results found", total);
+        }
+    }
+
+    fn raw(&mut self, response_text: &str) {
+        println!("{}", response_text); // Output raw response if not valid JSON
     }
 }
 
-/// Handle explain schema command
-fn handle_explain_schema(trimmed_input: &str, api_url: &str, token: &str, args: &Args) {
-    let result = api::dispatch_command(trimmed_input, api_url, token, args.verbose);
-    match result {
-        Ok(response_text) => {
-            // Parse and pretty print JSON response
-            match serde_json::from_str::<api::ExplainSchemaResponse>(&response_text) {
-                Ok(data) => {
-                    if args.verbose {
-                        println!("{}", "Command:".cyan());
-                        println!("{}", data.command);
-                        println!();
-                    }
-                    println!("{}", "Schema:".green());
-                    match serde_json::to_string_pretty(&data.schema) {
-                        Ok(pretty_json) => println!("{}", pretty_json),
-                        Err(_) => println!("{}", response_text), // Fallback to raw text
-                    }
-                }
-                Err(e) => {
-                    if args.verbose {
-                        eprintln!("❌ Failed to parse response as JSON: {}", e);
-                    }
-                    println!("{}", response_text); // Output raw response if not valid JSON
-                }
-            }
-        }
+/// Handle an FSQL command
+///
+/// Variables are substituted first, and the command isn't sent if any is
+/// unbound. Syntax errors found locally are shown as a warning, but the
+/// command is still sent: the API has the final say on what's valid.
+fn handle_command(
+    trimmed_input: &str,
+    connection: &Connection,
+    presenter: &mut ReplPresenter,
+    variables: &Variables,
+) {
+    let input = match variables.substitute(trimmed_input) {
+        Ok(input) => input,
         Err(e) => {
-            eprintln!("❌ Error dispatching command: {e}");
+            eprintln!("❌ {}", e);
+            println!("💡 Use \\set <name> <value> to set a variable");
+            return;
         }
+    };
+    if let Err(e) = fsql::parse(&input) {
+        eprintln!("{}", format!("⚠️  {}", e.render(&input)).yellow());
+    }
+    match command::execute_interruptible(&input, connection, presenter) {
+        Ok(()) => {}
+        Err(ApiError::Cancelled) => eprintln!("\n🛑 Cancelled"),
+        Err(e) => eprintln!("❌ Error dispatching command: {e}"),
     }
 }
 
-/// Handle explain attributes command
-fn handle_explain_attributes(trimmed_input: &str, api_url: &str, token: &str, args: &Args) {
-    let result = api::dispatch_command(trimmed_input, api_url, token, args.verbose);
-    match result {
-        Ok(response_text) => {
-            // Parse and pretty print JSON response
-            match serde_json::from_str::<api::ExplainAttributesResponse>(&response_text) {
-                Ok(data) => {
-                    if args.verbose {
-                        println!("{}", "Command:".cyan());
-                        println!("{}", data.command);
-                        println!();
-                    }
-                    println!("{}", "Attributes:".green());
-                    for attr in data.attributes.iter() {
-                        println!("{attr}");
-                    }
-                }
-                Err(e) => {
-                    if args.verbose {
-                        eprintln!("❌ Failed to parse response as JSON: {}", e);
-                    }
-                    println!("{}", response_text); // Output raw response if not valid JSON
-                }
-            }
+/// Handle the \format meta-command, which shows or sets the result format
+fn handle_format(argument: &str, presenter: &mut ReplPresenter) {
+    if argument.is_empty() {
+        println!("Output format: {}", presenter.output.format);
+        return;
+    }
+    match OutputFormat::from_str(argument, true) {
+        Ok(format) => {
+            presenter.output.format = format;
+            println!("Output format set to {}", format);
         }
-        Err(e) => {
-            eprintln!("❌ Error dispatching command: {e}");
+        Err(_) => eprintln!("❌ Unknown output format '{}'", argument),
+    }
+}
+
+/// Handle the \columns meta-command, which selects the result columns to display
+fn handle_columns(argument: &str, presenter: &mut ReplPresenter) {
+    if argument.is_empty() || argument == "*" {
+        presenter.output.columns = None;
+        println!("Showing all columns");
+    } else {
+        let columns: Vec<String> = argument
+            .split(',')
+            .map(|column| column.trim().to_string())
+            .filter(|column| !column.is_empty())
+            .collect();
+        println!("Showing columns: {}", columns.join(", "));
+        presenter.output.columns = Some(columns);
+    }
+}
+
+/// Handle the \pager meta-command, which shows or sets when the pager is used
+fn handle_pager(argument: &str, presenter: &mut ReplPresenter) {
+    if argument.is_empty() {
+        println!("Pager: {}", presenter.pager);
+        return;
+    }
+    match PagerMode::from_str(argument, true) {
+        Ok(mode) => {
+            presenter.pager = mode;
+            println!("Pager set to {}", mode);
         }
+        Err(_) => eprintln!(
+            "❌ Unknown pager setting '{}' (expected on, off or auto)",
+            argument
+        ),
     }
 }
 
-/// Handle explain command
-fn handle_explain(trimmed_input: &str, api_url: &str, token: &str, args: &Args) {
-    let result = api::dispatch_command(trimmed_input, api_url, token, args.verbose);
-    match result {
-        Ok(response_text) => {
-            // Parse and pretty print JSON response
-            match serde_json::from_str::<api::ExplainResponse>(&response_text) {
-                Ok(data) => {
-                    if args.verbose {
-                        println!("{}", "Original Input:".cyan());
-                        println!("{}", data.input);
-                        println!();
-                        println!("{}", "Command:".cyan());
-                        println!("{}", data.command);
-                        println!();
-                    }
-                    println!("{}", "Expanded Query:".green());
-                    // If the parsed value is a string, just print it so that the newline characters are
-                    // honoured. If not, use the pretty printer from serde_json
-                    match &data.expanded_query {
-                        serde_json::Value::String(s) => println!("{}", s),
-                        _ => match serde_json::to_string_pretty(&data.expanded_query) {
-                            Ok(pretty_json) => println!("{}", pretty_json),
-                            Err(_) => println!("{}", response_text), // Fallback to raw text
-                        },
-                    }
-                }
-                Err(e) => {
-                    if args.verbose {
-                        eprintln!("❌ Failed to parse response as JSON: {}", e);
-                    }
-                    println!("{}", response_text); // Output raw response if not valid JSON
-                }
-            }
+/// Handle the \\view meta-command, which opens the last QUERY results in the viewer
+fn handle_view(presenter: &ReplPresenter) {
+    let Some(data) = &presenter.last_results else {
+        eprintln!("❌ There are no results to view yet; run a QUERY first");
+        return;
+    };
+    if let Err(e) = viewer::show(data, &presenter.output) {
+        eprintln!("❌ {}", e);
+    }
+}
+
+/// Handle the \\o meta-command, which sends QUERY results to a file
+///
+/// Without a path, results go back to the terminal.
+fn handle_out_file(argument: &str, presenter: &mut ReplPresenter) {
+    if argument.is_empty() {
+        presenter.output.out_file = None;
+        println!("Results will be written to the terminal");
+    } else {
+        let path = PathBuf::from(argument);
+        println!(
+            "Results will be written to {} ({})",
+            path.display(),
+            presenter.output.format
+        );
+        presenter.output.out_file = Some(path);
+    }
+}
+
+/// Handle the \set meta-command, which shows or sets variables
+///
+/// The value is the rest of the line. It can be written as a string literal,
+/// which keeps anything that would otherwise be taken as a comment.
+fn handle_set(argument: &str, variables: &mut Variables) {
+    let (name, value) = argument
+        .split_once(char::is_whitespace)
+        .unwrap_or((argument, ""));
+    let value = value.trim();
+    if name.is_empty() {
+        if variables.is_empty() {
+            println!("No variables set");
         }
-        Err(e) => {
-            eprintln!("❌ Error dispatching command: {e}");
+        for (name, value) in variables.iter() {
+            println!("{} = {}", name, value);
+        }
+    } else if value.is_empty() {
+        match variables.get(name) {
+            Some(value) => println!("{} = {}", name, value),
+            None => eprintln!("❌ Variable '{}' is not set", name),
+        }
+    } else {
+        match variables.set(name, &vars::unquote(value)) {
+            Ok(()) => println!("{} = {}", name, variables.get(name).unwrap_or_default()),
+            Err(e) => eprintln!("❌ {}", e),
         }
     }
 }
 
-/// Handle query command
-fn handle_query(trimmed_input: &str, api_url: &str, token: &str, args: &Args) {
-    let result = api::dispatch_command(trimmed_input, api_url, token, args.verbose);
-    match result {
-        Ok(response_text) => {
-            // Parse and pretty print JSON response
-            match serde_json::from_str::<api::QueryResponse>(&response_text) {
-                Ok(data) => {
-                    if args.verbose {
-                        println!("{} {}", "Command:".cyan(), data.command);
-                        println!();
-                        println!("{} {}", "Trace ID:".cyan(), data.trace_id);
-                        println!();
-                    }
-                    println!("{} {}", "Search ID:".cyan(), data.search_id);
-                    println!();
-                    println!("{}", "Results:".green());
-                    match serde_json::to_string_pretty(&data.results) {
-                        Ok(pretty_json) => println!("{}", pretty_json),
-                        Err(_) => println!("{}", response_text), // Fallback to raw text
-                    }
-                    let total = data.results.len();
-                    if total == 1 {
-                        println!("{} result found", data.results.len());
-                    } else {
-                        println!("{} results found", data.results.len());
-                    }
-                }
-                Err(e) => {
-                    if args.verbose {
-                        eprintln!("❌ Failed to parse response as JSON: {}", e);
-                    }
-                    println!("{}", response_text); // Output raw response if not valid JSON
-                }
-            }
-        }
+/// Handle the \unset meta-command, which removes a variable
+fn handle_unset(argument: &str, variables: &mut Variables) {
+    if argument.is_empty() {
+        eprintln!("❌ Give the name of the variable to unset");
+    } else if variables.unset(argument) {
+        println!("Unset {}", argument);
+    } else {
+        eprintln!("❌ Variable '{}' is not set", argument);
+    }
+}
+
+/// Handle the \save meta-command, which saves the last command under a name
+///
+/// The rest of the line is the query's description. Saving over an existing
+/// query keeps its tags and default variables, and its description unless a
+/// new one is given.
+fn handle_save(argument: &str, last_command: Option<&str>) {
+    let (name, description) = argument
+        .split_once(char::is_whitespace)
+        .unwrap_or((argument, ""));
+    let Some(command) = last_command else {
+        eprintln!("❌ There's no command to save yet");
+        return;
+    };
+    if name.is_empty() {
+        eprintln!("❌ Give a name to save the last command as");
+        return;
+    }
+
+    let mut query = queries::load(name).unwrap_or_default();
+    query.query = command.to_string();
+    if !description.trim().is_empty() {
+        query.description = Some(description.trim().to_string());
+    }
+    match queries::save(name, &query) {
+        Ok(path) => println!("Saved {} to {}", name, path.display()),
+        Err(e) => eprintln!("❌ {}", e),
+    }
+}
+
+/// Handle the \load meta-command, which loads a saved query to edit and run
+///
+/// The query's default variables are set unless they're already set.
+/// Returns the query to put on the next prompt.
+fn handle_load(argument: &str, variables: &mut Variables) -> Option<String> {
+    if argument.is_empty() {
+        eprintln!("❌ Give the name of the query to load");
+        println!("💡 Use \\queries to list saved queries");
+        return None;
+    }
+    let query = match queries::load(argument) {
+        Ok(query) => query,
         Err(e) => {
-            eprintln!("❌ Error dispatching command: {e}");
+            eprintln!("❌ {}", e);
+            return None;
         }
+    };
+    match query.variables(variables) {
+        Ok(merged) => *variables = merged,
+        Err(e) => eprintln!("❌ {}", e),
     }
+    if let Some(description) = &query.description {
+        println!("{}", description.cyan());
+    }
+    Some(query.query)
+}
+
+/// If the input is the given backslash meta-command, return its argument
+fn meta_argument<'a>(input: &'a str, name: &str) -> Option<&'a str> {
+    let (command, argument) = input.split_once(char::is_whitespace).unwrap_or((input, ""));
+    command.eq_ignore_ascii_case(name).then(|| argument.trim())
 }
 
 /// Handle help command
@@ -335,7 +392,7 @@ fn handle_clear(api_url: &str) {
 }
 
 /// Handle exit command
-fn handle_exit(rl_editor: &mut DefaultEditor, history_path: &PathBuf) -> ! {
+fn handle_exit(rl_editor: &mut ReplEditor, history_path: &PathBuf) -> ! {
     println!();
     save_history_and_exit(rl_editor, history_path);
 }
@@ -347,19 +404,33 @@ fn handle_invalid_command() {
 }
 
 /// Launch an iteractive REPL to dispatch FSQL commands
-pub fn handle_repl(args: Args, token: &str) {
-    let api_url = format!("https://{}:{}/{}", args.host, args.port, args.path);
-    print_welcome(&api_url);
+pub fn handle_repl(
+    args: Args,
+    connection: &Connection,
+    mut variables: Variables,
+    pager: PagerMode,
+) {
+    let api_url = &connection.url;
+    print_welcome(api_url);
     print_help();
 
+    let mut presenter = ReplPresenter {
+        verbose: args.verbose,
+        output: OutputOptions::from_args(&args, OutputFormat::Table),
+        pager,
+        tui: args.tui,
+        last_results: None,
+    };
+
     // Initialize rustyline editor
-    let mut rl_editor = match DefaultEditor::new() {
+    let mut rl_editor: ReplEditor = match Editor::new() {
         Ok(editor) => editor,
         Err(e) => {
             eprintln!("Failed to initialize readline editor: {}", e);
             std::process::exit(1);
         }
     };
+    rl_editor.set_helper(Some(FsqlHelper::new(connection)));
 
     // Set up history file path
     let history_path = get_history_path();
@@ -367,6 +438,10 @@ pub fn handle_repl(args: Args, token: &str) {
     // Load existing history if the file exists
     let _ = rl_editor.load_history(&history_path);
 
+    // The last FSQL command sent, for \save, and a query from \load to edit
+    let mut last_command: Option<String> = None;
+    let mut loaded: Option<String> = None;
+
     loop {
         // Read multiline input
         let mut input = String::new();
@@ -380,21 +455,53 @@ pub fn handle_repl(args: Args, token: &str) {
                 format!("{:3}> ", line_count + 1)
             };
 
-            match rl_editor.readline(&prompt) {
+            let line = match loaded.take() {
+                Some(query) => rl_editor.readline_with_initial(&prompt, (&query, "")),
+                None => rl_editor.readline(&prompt),
+            };
+            match line {
                 Ok(line) => {
                     // Check for special commands on any line
                     let trimmed_line = line.trim();
                     let lower_line = trimmed_line.to_lowercase();
 
                     // If \reset is typed, exit the multiline query, reset the buffer, and clear the screen
-                    if line_count > 0 {
-                        if lower_line == "\\reset" {
-                            input.clear();
-                            line_count = 0;
-                            consecutive_empty_lines = 0;
-                            clearscreen::clear().expect("Failed to clear screen");
-                            continue;
+                    if line_count > 0 && lower_line == "\\reset" {
+                        input.clear();
+                        line_count = 0;
+                        consecutive_empty_lines = 0;
+                        clearscreen::clear().expect("Failed to clear screen");
+                        continue;
+                    }
+
+                    // \edit opens the query so far, or else the last one, in the
+                    // user's editor, and runs what's saved
+                    if lower_line == "\\edit" {
+                        let text = if line_count > 0 {
+                            input.trim_end().to_string()
+                        } else {
+                            rl_editor
+                                .history()
+                                .iter()
+                                .next_back()
+                                .cloned()
+                                .unwrap_or_default()
+                        };
+                        match editor::edit_text(&text) {
+                            Ok(edited) if !edited.trim().is_empty() => {
+                                println!("{}", edited.trim_end());
+                                input = edited;
+                                break;
+                            }
+                            Ok(_) => {
+                                println!("Nothing to run");
+                                input.clear();
+                                line_count = 0;
+                                consecutive_empty_lines = 0;
+                            }
+                            Err(e) => eprintln!("❌ Failed to edit the query: {}", e),
                         }
+                        continue;
                     }
 
                     input.push_str(&line);
@@ -409,18 +516,18 @@ pub fn handle_repl(args: Args, token: &str) {
                     }
 
                     // There are three means of exiting the loop here:
-                    // 1. Someone issues a single line command (i.e. help or exit)
+                    // 1. Someone issues a single line command (i.e. help, exit or
+                    //    a backslash meta-command with its argument)
                     // 2. They include an empty newline
-                    // 3. The line ends with a semicolon
-                    if consecutive_empty_lines >= 1 {
-                        break;
-                    } else if line_count == 1
+                    // 3. The line ends with a semicolon, perhaps followed by a comment
+                    let single_line_command = line_count == 1
                         && !trimmed_line.is_empty()
-                        && !trimmed_line.contains(' ')
+                        && (!trimmed_line.contains(' ') || trimmed_line.starts_with('\\'));
+                    if single_line_command
+                        || consecutive_empty_lines >= 1
+                        || ends_with_semicolon(&input)
                     {
                         break;
-                    } else if trimmed_line.ends_with(';') {
-                        break;
                     }
 
                     // If this is the first line and it's empty, break to handle it as a command
@@ -448,41 +555,55 @@ pub fn handle_repl(args: Args, token: &str) {
             }
         }
 
-        let trimmed_input = input.trim();
+        let entered = input.trim();
 
         // Skip empty input
-        if trimmed_input.is_empty() {
+        if entered.is_empty() {
             continue;
         }
 
-        // Add non-empty commands to history (ignore error if add fails)
-        let _ = rl_editor.add_history_entry(trimmed_input);
+        // Add non-empty commands to history (ignore error if add fails).
+        // History keeps the comments; they're removed before dispatch
+        let _ = rl_editor.add_history_entry(entered);
+
+        let stripped = fsql::strip_comments(entered);
+        let trimmed_input = stripped.trim();
+        if trimmed_input.is_empty() {
+            continue;
+        }
 
         let lower_input = trimmed_input.to_lowercase();
 
         // Process the complete input (use cleaned input for API calls)
-        if lower_input.starts_with("validate ") {
-            handle_validate(trimmed_input, &api_url, token, &args);
-        } else if lower_input.starts_with("summarize ") {
-            handle_summarize(trimmed_input, &api_url, token, &args);
-        } else if lower_input.starts_with("explain schema ") {
-            handle_explain_schema(trimmed_input, &api_url, token, &args);
-        } else if lower_input.starts_with("explain graphql ") {
-            handle_explain_graphql(trimmed_input, &api_url, token, &args);
-        } else if lower_input.starts_with("explain version") {
-            handle_explain_version(trimmed_input, &api_url, token, &args);
-        } else if lower_input.starts_with("explain connectors") {
-            handle_explain_connectors(trimmed_input, &api_url, token, &args);
-        } else if lower_input.starts_with("explain attributes ") {
-            handle_explain_attributes(trimmed_input, &api_url, token, &args);
-        } else if lower_input.starts_with("explain ") {
-            handle_explain(trimmed_input, &api_url, token, &args);
-        } else if lower_input.starts_with("query ") {
-            handle_query(trimmed_input, &api_url, token, &args);
+        if FsqlCommand::parse(trimmed_input).is_some() {
+            handle_command(trimmed_input, connection, &mut presenter, &variables);
+            last_command = Some(entered.to_string());
+        } else if let Some(argument) = meta_argument(trimmed_input, "\\format") {
+            handle_format(argument, &mut presenter);
+        } else if let Some(argument) = meta_argument(trimmed_input, "\\columns") {
+            handle_columns(argument, &mut presenter);
+        } else if let Some(argument) = meta_argument(trimmed_input, "\\o") {
+            handle_out_file(argument, &mut presenter);
+        } else if let Some(argument) = meta_argument(trimmed_input, "\\pager") {
+            handle_pager(argument, &mut presenter);
+        } else if lower_input == "\\view" {
+            handle_view(&presenter);
+        } else if let Some(argument) = meta_argument(trimmed_input, "\\set") {
+            handle_set(argument, &mut variables);
+        } else if let Some(argument) = meta_argument(trimmed_input, "\\unset") {
+            handle_unset(argument, &mut variables);
+        } else if let Some(argument) = meta_argument(trimmed_input, "\\save") {
+            handle_save(argument, last_command.as_deref());
+        } else if let Some(argument) = meta_argument(trimmed_input, "\\load") {
+            loaded = handle_load(argument, &mut variables);
+        } else if let Some(argument) = meta_argument(trimmed_input, "\\queries") {
+            queries::print_list((!argument.is_empty()).then_some(argument));
+        } else if lower_input == "\\whoami" {
+            jwt::print_whoami(&connection.token);
         } else if lower_input == "help" || lower_input == "h" {
             handle_help();
         } else if lower_input == "clear" {
-            handle_clear(&api_url);
+            handle_clear(api_url);
         } else if lower_input == "exit" {
             handle_exit(&mut rl_editor, &history_path);
         } else {
@@ -491,6 +612,21 @@ pub fn handle_repl(args: Args, token: &str) {
     }
 }
 
+/// Whether the input ends with `;`, ignoring any comments after it
+///
+/// A block comment that hasn't been closed yet keeps the input open.
+fn ends_with_semicolon(input: &str) -> bool {
+    fsql::lexer::tokenize(input)
+        .iter()
+        .rev()
+        .find(|token| {
+            let text = token.text(input);
+            token.kind != TokenKind::Comment
+                || (text.starts_with("/*") && !text[2..].ends_with("*/"))
+        })
+        .is_some_and(|token| token.kind == TokenKind::Semicolon)
+}
+
 /// Return the path where the RELP history is stored
 fn get_history_path() -> PathBuf {
     dirs::home_dir()
@@ -502,7 +638,7 @@ fn get_history_path() -> PathBuf {
 }
 
 /// Saves REPL history, prints a goodbye message, and exits
-fn save_history_and_exit(rl_editor: &mut DefaultEditor, history_path: &PathBuf) -> ! {
+fn save_history_and_exit(rl_editor: &mut ReplEditor, history_path: &PathBuf) -> ! {
     // Save history before exit
     if let Err(e) = rl_editor.save_history(history_path) {
         eprintln!("Warning: Failed to save history: {}", e);
@@ -515,11 +651,35 @@ fn save_history_and_exit(rl_editor: &mut DefaultEditor, history_path: &PathBuf)
 /// Print the REPL command list
 fn print_help() {
     println!("📚 {}", "FSQL REPL Help:".cyan());
+    println!("   QUERY <fsql>                - Execute a query and show the results");
+    println!("   VALIDATE <fsql>             - Check whether a query has valid syntax");
+    println!("   SUMMARIZE <fsql>            - Get summary information about a query");
     println!("   EXPLAIN CONNECTORS          - Get details about all configured connectors");
     println!("   EXPLAIN VERSION             - List FSQL and QDM versions");
+    println!("   EXPLAIN SCHEMA <path>       - Describe the schema for the given path");
     println!("   EXPLAIN ATTRIBUTES <fsql>   - Get a list of explanded attributes");
     println!("   EXPLAIN <fsql>              - Get query execution details");
     println!("   EXPLAIN GRAPHQL <fsql>      - Show the graphql translation of the given FSQL");
+    println!("   \\format [format]            - Show or set the format of QUERY results");
+    println!("   \\columns [col,...]          - Choose the result columns to display");
+    println!(
+        "   \\o [path]                   - Write QUERY results to a file (or back to the terminal)"
+    );
+    println!(
+        "   \\set [name [value]]         - Show or set variables for ${{name}} / :name placeholders"
+    );
+    println!("   \\unset <name>               - Remove a variable");
+    println!(
+        "   \\view                       - Explore the last QUERY results in a full-screen viewer"
+    );
+    println!("   \\pager [on|off|auto]        - Show or set when long output goes through $PAGER");
+    println!(
+        "   \\edit                       - Edit the current or last query in $VISUAL / $EDITOR"
+    );
+    println!("   \\save <name> [description]  - Save the last command to the query library");
+    println!("   \\load <name>                - Load a saved query to edit and run");
+    println!("   \\queries [tag]              - List saved queries");
+    println!("   \\whoami                     - Show who the token belongs to and when it expires");
     println!("   help, h                     - Show this help message");
     println!("   clear                       - Clear the screen");
     println!("   exit                        - Exit the REPL");
@@ -530,11 +690,14 @@ fn print_tips() {
     println!("💡 {}", "Tips:".cyan());
     println!("  • Multiline queries can be pasted");
     println!("  • Use \\reset to clear a query without submitting it");
+    println!("  • Use \\edit to write a long query in your editor");
+    println!("  • Press Ctrl+C to cancel a command that's taking too long");
     println!("  • Hit enter twice to send your command to the FSQL API");
     println!("  • End a command with ';' to end multiline input and send your command");
     println!("  • Press Ctrl+D (Unix) or Ctrl+Z (Windows) to exit");
     println!("  • Use Up/Down arrows to navigate command history");
     println!("  • Use Ctrl+R for reverse history search");
+    println!("  • Press Tab to complete keywords, meta-commands, event classes and attributes");
 }
 
 /// Print the welcome text
@@ -554,7 +717,7 @@ fn print_welcome(api_url: &str) {
 
 /// Select a random goodbye message and print it
 fn print_goodbye() {
-    let exit_messages = vec![
+    let exit_messages = [
         "Query ya later!",
         "Ya'll query again now, ya hear?",
         "Catch you on the Query side!",
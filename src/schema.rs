@@ -0,0 +1,270 @@
+//! A cache of the FSQL schema used for completing attribute paths
+//!
+//! The schema is fetched lazily with `EXPLAIN SCHEMA`: the list of event
+//! classes when it's first needed, and the attributes beneath an object the
+//! first time a path descends into it (unless an earlier response already
+//! included them). Each path is only ever fetched once, even if the request
+//! fails, so a missing or unreachable API doesn't stall completion.
+//!
+//! A cache can also be loaded from a file holding the schema printed by
+//! `fsqlctl -c "EXPLAIN SCHEMA *"`, so it can be used without the API.
+
+use crate::api::{self, Connection, ExplainSchemaResponse, FsqlResponse};
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Command used to list every event class
+const CLASSES_QUERY: &str = "EXPLAIN SCHEMA *";
+
+/// How long to wait for the schema before giving up on completion
+const FETCH_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Name of the schema file in the config directory
+const CACHE_FILE: &str = "schema.json";
+
+/// Keys under which a typed attribute may describe its nested attributes
+const NESTED_KEYS: &[&str] = &["attributes", "properties", "fields"];
+
+/// A class or attribute in the schema
+#[derive(Debug, Default)]
+struct Node {
+    children: BTreeMap<String, Node>,
+    /// Whether the children have been fetched (or are known)
+    loaded: bool,
+}
+
+impl Node {
+    /// Add the attributes described by a schema map to this node
+    fn merge(&mut self, attributes: &Map<String, Value>) {
+        for (name, value) in attributes {
+            let child = self.children.entry(name.clone()).or_default();
+            if let Some(nested) = nested_attributes(value) {
+                child.merge(nested);
+                child.loaded = true;
+            }
+        }
+    }
+
+    /// Number of attributes beneath this node, at any depth
+    fn count(&self) -> usize {
+        self.children.values().map(|child| 1 + child.count()).sum()
+    }
+}
+
+/// Get the attributes nested beneath an attribute's schema entry, if any
+///
+/// An object is taken to be a map of nested attributes, unless it has a
+/// `type` field, in which case the nested attributes are looked for under one
+/// of [`NESTED_KEYS`].
+fn nested_attributes(value: &Value) -> Option<&Map<String, Value>> {
+    let map = value.as_object()?;
+    if !map.get("type").is_some_and(Value::is_string) {
+        return Some(map);
+    }
+    NESTED_KEYS
+        .iter()
+        .find_map(|key| map.get(*key).and_then(Value::as_object))
+}
+
+/// Event classes and attributes known to the API
+pub struct SchemaCache {
+    /// Used to fetch missing parts of the schema; `None` never fetches
+    connection: Option<Connection>,
+    root: Node,
+}
+
+impl SchemaCache {
+    /// Create an empty cache that fetches from the API as needed
+    pub fn new(connection: Option<Connection>) -> Self {
+        let connection = connection.map(|connection| Connection {
+            timeout: connection.timeout.min(FETCH_TIMEOUT),
+            verbose: false,
+            ..connection
+        });
+        Self {
+            connection,
+            root: Node::default(),
+        }
+    }
+
+    /// Location of the schema file in the config directory
+    pub fn default_file() -> PathBuf {
+        crate::config::get_config_dir().join(CACHE_FILE)
+    }
+
+    /// Load a cache from a schema file, without fetching anything more
+    ///
+    /// The file holds a JSON object of event classes and their attributes,
+    /// as printed by `EXPLAIN SCHEMA *` in a pipe.
+    pub fn from_file(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let schema: HashMap<String, HashMap<String, Value>> = serde_json::from_str(&text)
+            .map_err(|e| format!("Invalid schema in {}: {}", path.display(), e))?;
+        let mut cache = Self::new(None);
+        cache.merge(
+            "",
+            &ExplainSchemaResponse {
+                command: CLASSES_QUERY.to_string(),
+                schema,
+            },
+        );
+        Ok(cache)
+    }
+
+    fn node(&self, path: &str) -> Option<&Node> {
+        segments(path).try_fold(&self.root, |node, name| node.children.get(name))
+    }
+
+    fn node_mut(&mut self, path: &str) -> &mut Node {
+        segments(path).fold(&mut self.root, |node, name| {
+            node.children.entry(name.to_string()).or_default()
+        })
+    }
+
+    /// Add an `EXPLAIN SCHEMA` response for the given path to the cache
+    ///
+    /// With an empty path the response lists event classes; otherwise its
+    /// attributes are added beneath the path.
+    pub fn merge(&mut self, path: &str, response: &ExplainSchemaResponse) {
+        let node = self.node_mut(path);
+        node.loaded = true;
+        for (name, attributes) in &response.schema {
+            let attributes: Map<String, Value> = attributes
+                .iter()
+                .map(|(key, value)| (key.clone(), value.clone()))
+                .collect();
+            if path.is_empty() {
+                let class = node.children.entry(name.clone()).or_default();
+                class.merge(&attributes);
+                class.loaded = !attributes.is_empty();
+            } else {
+                node.merge(&attributes);
+            }
+        }
+    }
+
+    /// Fetch the part of the schema beneath a path
+    fn load(&mut self, path: &str) {
+        self.node_mut(path).loaded = true;
+        let Some(connection) = &self.connection else {
+            return;
+        };
+        let query = if path.is_empty() {
+            CLASSES_QUERY.to_string()
+        } else {
+            format!("EXPLAIN SCHEMA {}", path)
+        };
+        if let Ok(FsqlResponse::ExplainSchema(data)) = api::dispatch_command(&query, connection) {
+            self.merge(path, &data);
+        }
+    }
+
+    /// Names of the classes (for an empty path) or attributes beneath a path
+    ///
+    /// Fetches the children of a known class or attribute that haven't been
+    /// loaded yet. Unknown paths have no children.
+    pub fn children(&mut self, path: &str) -> Vec<String> {
+        if self.node(path).is_some_and(|node| !node.loaded) {
+            self.load(path);
+        }
+        self.node(path)
+            .map(|node| node.children.keys().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Whether an attribute path could exist, as far as the cache knows
+    ///
+    /// A path is only unknown when it names something missing beneath a
+    /// class or attribute whose children are all known. Wildcards match
+    /// anything.
+    pub fn is_known(&self, path: &str) -> bool {
+        let mut node = &self.root;
+        for segment in segments(path) {
+            if segment.contains('*') || !node.loaded {
+                return true;
+            }
+            match node.children.get(segment) {
+                Some(child) => node = child,
+                None => return false,
+            }
+        }
+        true
+    }
+
+    /// Number of attributes known beneath an event class
+    pub fn attribute_count(&self, class: &str) -> usize {
+        self.node(class).map_or(0, Node::count)
+    }
+}
+
+/// Split a dotted path into its parts
+fn segments(path: &str) -> impl Iterator<Item = &str> {
+    path.split('.').filter(|segment| !segment.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn response(json: &str) -> ExplainSchemaResponse {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn test_merge_nested_schema() {
+        let mut cache = SchemaCache::new(None);
+        cache.merge(
+            "",
+            &response(
+                r#"{"command": "EXPLAIN SCHEMA", "schema": {
+                    "module_activity": {
+                        "time": "timestamp",
+                        "actor": {"process": {"type": "object", "attributes": {"pid": "integer"}}}
+                    },
+                    "file_activity": {}
+                }}"#,
+            ),
+        );
+
+        assert_eq!(cache.children(""), vec!["file_activity", "module_activity"]);
+        assert_eq!(cache.children("module_activity"), vec!["actor", "time"]);
+        assert_eq!(cache.children("module_activity.actor"), vec!["process"]);
+        assert_eq!(cache.children("module_activity.actor.process"), vec!["pid"]);
+        assert!(cache.children("module_activity.time").is_empty());
+        assert!(cache.children("file_activity").is_empty());
+        assert!(cache.children("missing.path").is_empty());
+
+        // A later response fills in a class that was listed without attributes
+        cache.merge(
+            "file_activity",
+            &response(
+                r#"{"command": "EXPLAIN SCHEMA", "schema": {"file_activity": {"file": {}}}}"#,
+            ),
+        );
+        assert_eq!(cache.children("file_activity"), vec!["file"]);
+    }
+
+    #[test]
+    fn test_known_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("schema.json");
+        std::fs::write(
+            &path,
+            r#"{"module_activity": {"actor": {"process": {"pid": "integer"}}}, "file_activity": {}}"#,
+        )
+        .unwrap();
+        let cache = SchemaCache::from_file(&path).unwrap();
+
+        assert!(cache.is_known("module_activity.actor.process.pid"));
+        assert!(cache.is_known("module_activity.actor.**"));
+        assert!(!cache.is_known("module_activity.actor.user"));
+        assert!(!cache.is_known("network_activity.src_endpoint"));
+        // Nothing is known about the attributes of file_activity
+        assert!(cache.is_known("file_activity.file.name"));
+        assert_eq!(cache.attribute_count("module_activity"), 3);
+        assert!(SchemaCache::from_file(&dir.path().join("missing.json")).is_err());
+    }
+}
@@ -0,0 +1,171 @@
+//! Encryption of tokens stored in the config file
+//!
+//! When `encrypt-tokens` is enabled, tokens are written to the config file as
+//! `enc:v1:<base64>` rather than in plaintext. The encoded data is a random
+//! salt and nonce followed by the XChaCha20-Poly1305 ciphertext, with the key
+//! derived from a passphrase using Argon2id.
+//!
+//! The passphrase is read from `FSQL_PASSPHRASE`, or prompted for on the
+//! terminal when that isn't set.
+
+use argon2::Argon2;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use rand::Rng;
+
+/// Marks a value in the config file as an encrypted token
+const ENCRYPTED_PREFIX: &str = "enc:v1:";
+
+/// Environment variable that supplies the passphrase without prompting
+pub const PASSPHRASE_ENV: &str = "FSQL_PASSPHRASE";
+
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 24;
+
+/// Whether a stored value is an encrypted token
+pub fn is_encrypted(value: &str) -> bool {
+    value.starts_with(ENCRYPTED_PREFIX)
+}
+
+/// Derive the encryption key for a passphrase and salt
+fn derive_key(passphrase: &str, salt: &[u8]) -> Result<XChaCha20Poly1305, String> {
+    let mut key = [0u8; 32];
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| format!("Failed to derive key from passphrase: {}", e))?;
+    Ok(XChaCha20Poly1305::new(&key.into()))
+}
+
+/// Encrypt a token with the given passphrase
+pub fn encrypt(token: &str, passphrase: &str) -> Result<String, String> {
+    let mut salt = [0u8; SALT_LEN];
+    let mut nonce = [0u8; NONCE_LEN];
+    let mut rng = rand::rng();
+    rng.fill(&mut salt);
+    rng.fill(&mut nonce);
+
+    let ciphertext = derive_key(passphrase, &salt)?
+        .encrypt(XNonce::from_slice(&nonce), token.as_bytes())
+        .map_err(|_| "Failed to encrypt token".to_string())?;
+
+    let mut data = Vec::with_capacity(SALT_LEN + NONCE_LEN + ciphertext.len());
+    data.extend_from_slice(&salt);
+    data.extend_from_slice(&nonce);
+    data.extend_from_slice(&ciphertext);
+    Ok(format!("{}{}", ENCRYPTED_PREFIX, BASE64.encode(data)))
+}
+
+/// Decrypt a token produced by [`encrypt`]
+///
+/// Fails if the value is malformed or the passphrase is wrong.
+pub fn decrypt(value: &str, passphrase: &str) -> Result<String, String> {
+    let encoded = value
+        .strip_prefix(ENCRYPTED_PREFIX)
+        .ok_or_else(|| "Token is not encrypted".to_string())?;
+    let data = BASE64
+        .decode(encoded)
+        .map_err(|e| format!("Encrypted token is corrupt: {}", e))?;
+    if data.len() < SALT_LEN + NONCE_LEN {
+        return Err("Encrypted token is corrupt: too short".to_string());
+    }
+    let (salt, rest) = data.split_at(SALT_LEN);
+    let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+
+    let plaintext = derive_key(passphrase, salt)?
+        .decrypt(XNonce::from_slice(nonce), ciphertext)
+        .map_err(|_| "Failed to decrypt token - is the passphrase correct?".to_string())?;
+    String::from_utf8(plaintext).map_err(|_| "Decrypted token is not valid UTF-8".to_string())
+}
+
+/// Get the passphrase from the environment or by prompting on the terminal
+///
+/// With `confirm`, a prompted passphrase must be typed twice.
+pub fn passphrase(confirm: bool) -> Result<String, String> {
+    if let Ok(passphrase) = std::env::var(PASSPHRASE_ENV) {
+        return Ok(passphrase);
+    }
+    let prompt = |text: &str| {
+        rpassword::prompt_password(text).map_err(|e| format!("Failed to read passphrase: {}", e))
+    };
+    let passphrase = prompt("🔐 Token passphrase: ")?;
+    if passphrase.is_empty() {
+        return Err("The passphrase can't be empty".to_string());
+    }
+    if confirm && prompt("🔐 Repeat passphrase: ")? != passphrase {
+        return Err("Passphrases don't match".to_string());
+    }
+    Ok(passphrase)
+}
+
+/// Get the plaintext of a stored token, asking for the passphrase if it's
+/// encrypted
+pub fn reveal(value: &str) -> Result<String, String> {
+    if is_encrypted(value) {
+        decrypt(value, &passphrase(false)?)
+    } else {
+        Ok(value.to_string())
+    }
+}
+
+/// Whether a stored value already holds a token, so it needn't be saved again
+///
+/// An encrypted value is only decrypted with a passphrase from the
+/// environment; without one it's assumed to hold the token rather than
+/// asking for the passphrase on every run.
+pub fn holds_token(stored: &str, token: &str, passphrase: Option<&str>) -> bool {
+    if !is_encrypted(stored) {
+        return stored == token;
+    }
+    match passphrase.map(|passphrase| decrypt(stored, passphrase)) {
+        Some(Ok(plaintext)) => plaintext == token,
+        // A value the passphrase can't decrypt is left for the user to fix
+        Some(Err(_)) | None => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let encrypted = encrypt("eyJhbGciOiJIUzI1NiJ9.e30.sig", "hunter2").unwrap();
+        assert!(is_encrypted(&encrypted));
+        assert!(!encrypted.contains("eyJ"));
+        assert_eq!(
+            decrypt(&encrypted, "hunter2").unwrap(),
+            "eyJhbGciOiJIUzI1NiJ9.e30.sig"
+        );
+
+        // Each encryption uses a fresh salt and nonce
+        assert_ne!(
+            encrypted,
+            encrypt("eyJhbGciOiJIUzI1NiJ9.e30.sig", "hunter2").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_decrypt_failures() {
+        let encrypted = encrypt("token", "hunter2").unwrap();
+        assert!(decrypt(&encrypted, "wrong").is_err());
+        assert!(decrypt("token", "hunter2").is_err());
+        assert!(decrypt("enc:v1:!!!", "hunter2").is_err());
+        assert!(decrypt("enc:v1:AAAA", "hunter2").is_err());
+        assert!(!is_encrypted("token"));
+    }
+
+    #[test]
+    fn test_holds_token() {
+        assert!(holds_token("token", "token", None));
+        assert!(!holds_token("token", "other", Some("hunter2")));
+
+        let encrypted = encrypt("token", "hunter2").unwrap();
+        assert!(holds_token(&encrypted, "token", Some("hunter2")));
+        assert!(!holds_token(&encrypted, "other", Some("hunter2")));
+        // Without a passphrase to check with, the stored token is kept
+        assert!(holds_token(&encrypted, "other", None));
+        assert!(holds_token(&encrypted, "other", Some("wrong")));
+    }
+}
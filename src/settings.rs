@@ -0,0 +1,198 @@
+//! The `fsqlctl config` subcommands
+//!
+//! These read and change the config file without having to edit it by hand.
+//! Settings are named by dotted keys, e.g. `api-keys.api.query.ai` or
+//! `profiles.prod.host`. Token values are masked unless asked for.
+
+use crate::config::{self, Config};
+use crate::editor;
+use crate::secrets;
+use clap::Subcommand;
+use colored::Colorize;
+
+#[derive(Subcommand, Debug)]
+pub enum ConfigCommand {
+    #[command(about = "List every setting in the config file")]
+    List {
+        #[arg(long, help = "Show token values instead of masking them")]
+        show_tokens: bool,
+    },
+
+    #[command(about = "Print the value of a single setting")]
+    Get {
+        #[arg(
+            help = "Setting to print, e.g. default-profile, api-keys.<host> or profiles.<name>.host"
+        )]
+        key: String,
+
+        #[arg(long, help = "Show token values instead of masking them")]
+        show_tokens: bool,
+    },
+
+    #[command(about = "Change a setting")]
+    Set {
+        #[arg(help = "Setting to change")]
+        key: String,
+
+        #[arg(help = "New value for the setting")]
+        value: String,
+    },
+
+    #[command(about = "Remove a setting, or a whole profile with profiles.<name>")]
+    Unset {
+        #[arg(help = "Setting to remove")]
+        key: String,
+    },
+
+    #[command(about = "Print the location of the config file")]
+    Path,
+
+    #[command(about = "Encrypt stored tokens with a passphrase and keep new tokens encrypted")]
+    Encrypt,
+
+    #[command(about = "Decrypt stored tokens and store new tokens in plaintext")]
+    Decrypt,
+
+    #[command(about = "Open the config file in $VISUAL or $EDITOR")]
+    Edit,
+}
+
+/// Print an error and exit
+fn fail(message: impl std::fmt::Display) -> ! {
+    eprintln!("{}", format!("❌ {}", message).red());
+    std::process::exit(1);
+}
+
+fn load() -> Config {
+    Config::load().unwrap_or_else(|e| {
+        fail(format_args!(
+            "Failed to load {}: {}",
+            Config::get_config_location(),
+            e
+        ))
+    })
+}
+
+fn save(config: &Config) {
+    if let Err(e) = config.save() {
+        fail(format_args!("Failed to save config: {}", e));
+    }
+}
+
+/// Run a `fsqlctl config` subcommand
+pub fn handle_config(command: ConfigCommand) {
+    match command {
+        ConfigCommand::List { show_tokens } => {
+            // Only ask for the passphrase once, and only if it's needed
+            let mut passphrase = None;
+            for (key, value) in load().entries(show_tokens) {
+                if show_tokens && secrets::is_encrypted(&value) {
+                    let passphrase = passphrase.get_or_insert_with(|| {
+                        secrets::passphrase(false).unwrap_or_else(|e| fail(e))
+                    });
+                    let token = secrets::decrypt(&value, passphrase).unwrap_or_else(|e| fail(e));
+                    println!("{} = {}", key, token);
+                } else {
+                    println!("{} = {}", key, value);
+                }
+            }
+        }
+        ConfigCommand::Get { key, show_tokens } => {
+            let config = load();
+            match config.get(&key) {
+                Ok(Some(value)) => {
+                    if config::is_token_key(&key) && !show_tokens {
+                        if secrets::is_encrypted(&value) {
+                            println!("(encrypted)");
+                        } else {
+                            println!("{}", config::mask_token(&value));
+                        }
+                    } else if config::is_token_key(&key) {
+                        println!("{}", secrets::reveal(&value).unwrap_or_else(|e| fail(e)));
+                    } else {
+                        println!("{}", value);
+                    }
+                }
+                Ok(None) => fail(format_args!("'{}' is not set", key)),
+                Err(e) => fail(e),
+            }
+        }
+        ConfigCommand::Set { key, mut value } => {
+            let mut config = load();
+            if config.encrypt_tokens && config::is_token_key(&key) {
+                value = secrets::passphrase(false)
+                    .and_then(|passphrase| secrets::encrypt(&value, &passphrase))
+                    .unwrap_or_else(|e| fail(e));
+            }
+            if let Err(e) = config.set(&key, &value) {
+                fail(e);
+            }
+            save(&config);
+        }
+        ConfigCommand::Unset { key } => {
+            let mut config = load();
+            match config.unset(&key) {
+                Ok(true) => save(&config),
+                Ok(false) => fail(format_args!("'{}' is not set", key)),
+                Err(e) => fail(e),
+            }
+        }
+        ConfigCommand::Path => println!("{}", Config::get_config_location()),
+        ConfigCommand::Encrypt => encrypt(),
+        ConfigCommand::Decrypt => decrypt(),
+        ConfigCommand::Edit => edit(),
+    }
+}
+
+/// Encrypt every plaintext token and turn on `encrypt-tokens`
+///
+/// Tokens that are already encrypted must use the same passphrase.
+fn encrypt() {
+    let mut config = load();
+    let passphrase = secrets::passphrase(true).unwrap_or_else(|e| fail(e));
+    let mut count = 0;
+    for token in config.tokens_mut() {
+        if secrets::is_encrypted(token) {
+            // Check the passphrase matches rather than mixing passphrases
+            secrets::decrypt(token, &passphrase).unwrap_or_else(|e| fail(e));
+        } else {
+            *token = secrets::encrypt(token, &passphrase).unwrap_or_else(|e| fail(e));
+            count += 1;
+        }
+    }
+    config.encrypt_tokens = true;
+    save(&config);
+    println!("🔐 Encrypted {} stored token(s)", count);
+}
+
+/// Decrypt every stored token and turn off `encrypt-tokens`
+fn decrypt() {
+    let mut config = load();
+    let mut passphrase = None;
+    let mut count = 0;
+    for token in config.tokens_mut() {
+        if secrets::is_encrypted(token) {
+            let passphrase = passphrase
+                .get_or_insert_with(|| secrets::passphrase(false).unwrap_or_else(|e| fail(e)));
+            *token = secrets::decrypt(token, passphrase).unwrap_or_else(|e| fail(e));
+            count += 1;
+        }
+    }
+    config.encrypt_tokens = false;
+    save(&config);
+    println!("🔓 Decrypted {} stored token(s)", count);
+}
+
+/// Open the config file in an editor, then check that it still loads
+fn edit() {
+    let path = Config::get_config_path();
+    if !path.exists() {
+        save(&Config::default());
+    }
+    if let Err(e) = editor::edit_file(&path) {
+        fail(e);
+    }
+    if let Err(e) = Config::load() {
+        fail(format_args!("{} is no longer valid: {}", path.display(), e));
+    }
+}
@@ -1,397 +1,377 @@
-use crate::{Args, api};
+use crate::Args;
+use crate::api::{self, Connection};
+use crate::command::{self, FsqlCommand, Presenter};
+use crate::fsql::{self, script};
+use crate::output::{self, OutputFormat, OutputOptions};
+use crate::vars::Variables;
+use crate::viewer;
 use colored::Colorize;
-use serde_json;
 use std::fs;
-use std::io::{self, Read};
+use std::io::{self, IsTerminal, Read};
 
-/// Explain configured connectors
+/// Presents API responses when running as a stage in a pipeline
 ///
-/// Prints a summary of connectors
-fn handle_explain_connectors(input: &str, api_url: &str, token: &str, verbose: bool) {
-    let result = api::dispatch_command(input, api_url, token, verbose);
-    match result {
-        Ok(response_text) => {
-            // Parse and pretty print JSON response
-            match serde_json::from_str::<api::ExplainConnectorsResponse>(&response_text) {
-                Ok(data) => {
-                    if verbose {
-                        eprintln!("{}", "Command:".cyan());
-                        eprintln!("{}", data.command);
-                        eprintln!();
-                    }
-                    eprintln!("{}", "Connectors:");
-                    match serde_json::to_string_pretty(&data.connectors) {
-                        Ok(pretty_json) => println!("{}", pretty_json),
-                        Err(_) => println!("{}", response_text), // Fallback to raw text
-                    }
-                }
-                Err(e) => {
-                    if verbose {
-                        eprintln!("❌ Failed to parse response as JSON: {}", e);
-                    }
-                    eprintln!("{}", response_text); // Output raw response if not valid JSON
-                }
-            }
-        }
-        Err(e) => {
-            eprintln!("❌ Error dispatching command: {e}");
-            std::process::exit(1);
+/// User-facing messages are printed to stderr and the actual payload is
+/// written to stdout so that the tool will work in a pipeline.
+struct PipePresenter {
+    verbose: bool,
+    /// How QUERY results are written to stdout
+    output: OutputOptions,
+    /// Open QUERY results in the viewer instead of writing them to stdout
+    tui: bool,
+    /// Set when the response indicates the command did not succeed
+    failed: bool,
+}
+
+impl PipePresenter {
+    /// Print the command echoed back by the API when in verbose mode
+    fn print_command(&self, command: &str) {
+        if self.verbose {
+            eprintln!("{}", "Command:".cyan());
+            eprintln!("{}", command);
+            eprintln!();
         }
     }
 }
 
-/// Explain schema
-///
-/// Prints a description of the graphql schema for a given path
-fn handle_explain_schema(input: &str, api_url: &str, token: &str, verbose: bool) {
-    let result = api::dispatch_command(input, api_url, token, verbose);
-    match result {
-        Ok(response_text) => {
-            // Parse and pretty print JSON response
-            match serde_json::from_str::<api::ExplainSchemaResponse>(&response_text) {
-                Ok(data) => {
-                    if verbose {
-                        eprintln!("{}", "Command:".cyan());
-                        eprintln!("{}", data.command);
-                        eprintln!();
-                    }
-                    eprintln!("{}", "Schema:");
-                    match serde_json::to_string_pretty(&data.schema) {
-                        Ok(pretty_json) => println!("{}", pretty_json),
-                        Err(_) => println!("{}", response_text), // Fallback to raw text
-                    }
-                }
-                Err(e) => {
-                    if verbose {
-                        eprintln!("❌ Failed to parse response as JSON: {}", e);
-                    }
-                    eprintln!("{}", response_text); // Output raw response if not valid JSON
-                }
-            }
+impl Presenter for PipePresenter {
+    /// Prints a summary of connectors
+    fn explain_connectors(&mut self, data: &api::ExplainConnectorsResponse) {
+        self.print_command(&data.command);
+        eprintln!("Connectors:");
+        match serde_json::to_string_pretty(&data.connectors) {
+            Ok(pretty_json) => println!("{}", pretty_json),
+            Err(e) => self.raw(&e.to_string()),
         }
-        Err(e) => {
-            eprintln!("❌ Error dispatching command: {e}");
-            std::process::exit(1);
+    }
+
+    /// Prints version info from the API
+    fn explain_version(&mut self, data: &api::ExplainVersionResponse) {
+        eprintln!("Version Information:");
+        match serde_json::to_string_pretty(data) {
+            Ok(pretty_json) => println!("{}", pretty_json),
+            Err(e) => self.raw(&e.to_string()),
         }
     }
-}
 
-/// Explain attributes
-///
-/// Prints an expansion of the given attributes
-fn handle_explain_attributes(input: &str, api_url: &str, token: &str, verbose: bool) {
-    let result = api::dispatch_command(input, api_url, token, verbose);
-    match result {
-        Ok(response_text) => {
-            // Parse and pretty print JSON response
-            match serde_json::from_str::<api::ExplainAttributesResponse>(&response_text) {
-                Ok(data) => {
-                    if verbose {
-                        eprintln!("{}", "Command:".cyan());
-                        eprintln!("{}", data.command);
-                        eprintln!();
-                    }
-                    eprintln!("{}", "Attributes:");
-                    match serde_json::to_string_pretty(&data.attributes) {
-                        Ok(pretty_json) => println!("{}", pretty_json),
-                        Err(_) => println!("{}", response_text), // Fallback to raw text
-                    }
-                }
-                Err(e) => {
-                    if verbose {
-                        eprintln!("❌ Failed to parse response as JSON: {}", e);
-                    }
-                    eprintln!("{}", response_text); // Output raw response if not valid JSON
-                }
-            }
+    /// Prints a description of the graphql schema for a given path
+    fn explain_schema(&mut self, data: &api::ExplainSchemaResponse) {
+        self.print_command(&data.command);
+        eprintln!("Schema:");
+        match serde_json::to_string_pretty(&data.schema) {
+            Ok(pretty_json) => println!("{}", pretty_json),
+            Err(e) => self.raw(&e.to_string()),
         }
-        Err(e) => {
-            eprintln!("❌ Error dispatching command: {e}");
-            std::process::exit(1);
+    }
+
+    /// Prints the graphql version of a given FSQL query
+    fn explain_graphql(&mut self, data: &api::ExplainGraphqlResponse) {
+        eprintln!("Graphql Query:");
+        println!("{}", data.query);
+    }
+
+    /// Prints an expansion of the given attributes
+    fn explain_attributes(&mut self, data: &api::ExplainAttributesResponse) {
+        self.print_command(&data.command);
+        eprintln!("Attributes:");
+        match serde_json::to_string_pretty(&data.attributes) {
+            Ok(pretty_json) => println!("{}", pretty_json),
+            Err(e) => self.raw(&e.to_string()),
         }
     }
-}
 
-/// Explain version
-///
-/// Prints version info from the API
-fn handle_explain_version(input: &str, api_url: &str, token: &str, verbose: bool) {
-    let result = api::dispatch_command(input, api_url, token, verbose);
-    match result {
-        Ok(response_text) => {
-            // Parse and pretty print JSON response
-            match serde_json::from_str::<api::ExplainVersionResponse>(&response_text) {
-                Ok(data) => {
-                    eprintln!("{}", "Version Information:");
-                    match serde_json::to_string_pretty(&data) {
-                        Ok(pretty_json) => println!("{}", pretty_json),
-                        Err(_) => println!("{}", response_text), // Fallback to raw text
-                    }
-                }
-                Err(e) => {
-                    if verbose {
-                        eprintln!("❌ Failed to parse response as JSON: {}", e);
-                    }
-                    eprintln!("{}", response_text); // Output raw response if not valid JSON
-                }
-            }
+    /// Prints an expanded version of the query
+    fn explain(&mut self, data: &api::ExplainResponse) {
+        if self.verbose {
+            eprintln!("{}", "Original Input:".cyan());
+            eprintln!("{}", data.input);
+            eprintln!();
         }
-        Err(e) => {
-            eprintln!("❌ Error dispatching command: {e}");
-            std::process::exit(1);
+        self.print_command(&data.command);
+        eprintln!("Expanded Query:");
+        // If the parsed value is a string, just print it so that the newline characters are
+        // honoured. If not, use the pretty printer from serde_json
+        match &data.expanded_query {
+            serde_json::Value::String(s) => println!("{}", s),
+            _ => match serde_json::to_string_pretty(&data.expanded_query) {
+                Ok(pretty_json) => println!("{}", pretty_json),
+                Err(e) => self.raw(&e.to_string()),
+            },
         }
     }
-}
 
-/// Summarize
-///
-/// Prints summary information
-fn handle_summarize(input: &str, api_url: &str, token: &str, verbose: bool) {
-    let result = api::dispatch_command(input, api_url, token, verbose);
-    match result {
-        Ok(response_text) => {
-            match serde_json::from_str::<api::SummarizeResponse>(&response_text) {
-                Ok(data) => {
-                    eprintln!("{}", "Summarize Details:");
-                    match serde_json::to_string_pretty(&data) {
-                        Ok(pretty_json) => println!("{}", pretty_json),
-                        Err(_) => println!("{}", response_text), // Fallback to raw text
-                    }
-                }
-                Err(e) => {
-                    if verbose {
-                        eprintln!("❌ Failed to parse response as JSON: {}", e);
-                    }
-                    eprintln!("{}", response_text); // Output raw response if not valid JSON
-                }
-            }
+    /// Prints summary information
+    fn summarize(&mut self, data: &api::SummarizeResponse) {
+        eprintln!("Summarize Details:");
+        match serde_json::to_string_pretty(data) {
+            Ok(pretty_json) => println!("{}", pretty_json),
+            Err(e) => self.raw(&e.to_string()),
         }
-        Err(e) => {
-            eprintln!("❌ Error dispatching command: {e}");
-            std::process::exit(1);
+    }
+
+    /// Reports whether the query is valid; an invalid query is a failure
+    fn validate(&mut self, data: &api::ValidateResponse) {
+        self.print_command(&data.command);
+        // The invalid query part probably will never display given the current API because
+        // it doesn't actually return is_valid: false - it gives a different error with an
+        // error code. We should probably fix the API.
+        if data.is_valid {
+            eprintln!("✅ Query is valid");
+        } else {
+            eprintln!("❌ Query is invalid");
+            self.failed = true;
         }
     }
-}
 
-/// Explain graphql
-///
-/// Prints the graphql version of a given FSQL query
-fn handle_explain_graphql(input: &str, api_url: &str, token: &str, verbose: bool) {
-    let result = api::dispatch_command(input, api_url, token, verbose);
-    match result {
-        Ok(response_text) => {
-            // Parse and pretty print JSON response
-            match serde_json::from_str::<api::ExplainGraphqlResponse>(&response_text) {
-                Ok(data) => {
-                    eprintln!("{}", "Graphql Query:");
-                    println!("{}", data.query);
-                }
+    /// Writes the query results to stdout in the selected format
+    fn query(&mut self, data: &api::QueryResponse) {
+        if self.verbose {
+            eprintln!("{}", "Command:".cyan());
+            eprintln!("{}", data.command);
+            eprintln!();
+            eprintln!("{}", "Trace ID:".cyan());
+            eprintln!("{}", data.trace_id);
+            eprintln!();
+        }
+        eprintln!("{}", "Search ID:".cyan());
+        eprintln!("{}", data.search_id);
+        eprintln!();
+        eprintln!("Results:");
+        if let Some(path) = &self.output.out_file {
+            match output::write_results_to_file(path, data, &self.output) {
+                Ok(summary) => eprintln!("💾 {}", summary),
                 Err(e) => {
-                    if verbose {
-                        eprintln!("❌ Failed to parse response as JSON: {}", e);
-                    }
-                    eprintln!("{}", response_text); // Output raw response if not valid JSON
+                    eprintln!("❌ Failed to write results: {}", e);
+                    self.failed = true;
                 }
             }
+            return;
         }
-        Err(e) => {
-            eprintln!("❌ Error dispatching command: {e}");
-            std::process::exit(1);
+        if self.tui {
+            if let Err(e) = viewer::show(data, &self.output) {
+                eprintln!("❌ {}", e);
+                self.failed = true;
+            }
+            return;
         }
+
+        // Only squeeze tables to fit when they're going to a terminal
+        let width = io::stdout()
+            .is_terminal()
+            .then(output::terminal_width)
+            .flatten();
+        if let Err(e) = output::write_stdout(data, &self.output, width) {
+            eprintln!("❌ Failed to write results: {}", e);
+            self.failed = true;
+        }
+    }
+
+    fn raw(&mut self, response_text: &str) {
+        eprintln!("{}", response_text); // Output raw response if not valid JSON
+        self.failed = true;
     }
 }
 
-/// Explain an FSQL query
+/// Dispatch a single FSQL command and write the results to stdout
 ///
-/// Prints an expanded version of the query
-fn handle_explain(input: &str, api_url: &str, token: &str, verbose: bool) {
-    let result = api::dispatch_command(input, api_url, token, verbose);
-    match result {
-        Ok(response_text) => {
-            // Parse and pretty print JSON response
-            match serde_json::from_str::<api::ExplainResponse>(&response_text) {
-                Ok(data) => {
-                    if verbose {
-                        eprintln!("{}", "Original Input:".cyan());
-                        eprintln!("{}", data.input);
-                        eprintln!();
-                        eprintln!("{}", "Command:".cyan());
-                        eprintln!("{}", data.command);
-                        eprintln!();
-                    }
-                    eprintln!("{}", "Expanded Query:");
-                    // If the parsed value is a string, just print it so that the newline characters are
-                    // honoured. If not, use the pretty printer from serde_json
-                    match &data.expanded_query {
-                        serde_json::Value::String(s) => println!("{}", s),
-                        _ => match serde_json::to_string_pretty(&data.expanded_query) {
-                            Ok(pretty_json) => eprintln!("{}", pretty_json),
-                            Err(_) => eprintln!("{}", response_text), // Fallback to raw text
-                        },
-                    }
-                }
-                Err(e) => {
-                    if verbose {
-                        eprintln!("❌ Failed to parse response as JSON: {}", e);
-                    }
-                    eprintln!("{}", response_text); // Output raw response if not valid JSON
-                }
-            }
-        }
-        Err(e) => {
-            eprintln!("❌ Error dispatching command: {e}");
-            std::process::exit(1);
-        }
+/// Returns whether it succeeded: the command must be valid, the request must
+/// not fail, and the response must not indicate a failure. Comments are
+/// removed before the command is sent.
+fn run_command(input: &str, connection: &Connection, args: &Args) -> bool {
+    let input = fsql::strip_comments(input);
+    let input = input.trim();
+    if FsqlCommand::parse(input).is_none() {
+        eprintln!("(╯°□°)╯︵ ┻━┻ Invalid Command");
+        return false;
+    }
+
+    let mut presenter = PipePresenter {
+        verbose: args.verbose,
+        output: OutputOptions::from_args(args, OutputFormat::Json),
+        tui: args.tui,
+        failed: false,
+    };
+    if let Err(e) = command::execute(input, connection, &mut presenter) {
+        eprintln!("❌ Error dispatching command: {e}");
+        return false;
     }
+    !presenter.failed
 }
 
-/// Validate an FSQL query
+/// Dispatch a single FSQL command and write the results to stdout
 ///
-/// Dispatches a validation request to the FSQL API.
-fn handle_validate(input: &str, api_url: &str, token: &str, verbose: bool) {
-    let result = api::dispatch_command(input, api_url, token, verbose);
-    match result {
-        Ok(response_text) => {
-            match serde_json::from_str::<api::ValidateResponse>(&response_text) {
-                Ok(data) => {
-                    if verbose {
-                        eprintln!("{}", "Command:".cyan());
-                        eprintln!("{}", data.command);
-                        eprintln!();
-                    }
-                    // The invalid query part probably will never display given the current API because
-                    // it doesn't actually return is_valid: false - it gives a different error with an
-                    // error code. We should probably fix the API.
-                    if data.is_valid {
-                        eprintln!("✅ Query is valid");
-                        std::process::exit(0);
-                    } else {
-                        eprintln!("❌ Query is invalid");
-                        std::process::exit(1);
-                    }
-                }
-                Err(e) => {
-                    if verbose {
-                        eprintln!("❌ Failed to parse response as JSON: {}", e);
-                    }
-                    eprintln!("{}", response_text); // Output raw response if not valid JSON
-                    std::process::exit(1);
-                }
-            }
-        }
-        Err(e) => {
-            eprintln!("❌ Error dispatching command: {e}");
-            std::process::exit(1);
-        }
+/// Exits the process with a non-zero status if the command is invalid, the
+/// request fails, or the response indicates a failure.
+pub fn process_command(input: &str, connection: &Connection, args: &Args) {
+    if !run_command(input, connection, args) {
+        std::process::exit(1);
     }
 }
 
-/// Dispatch an FSQL query
+/// Run each `;` separated statement of a script in turn
 ///
-/// Dispatches a query to the FSQL API. User-facing messages are printed to
-/// stderr and the actual query results are written to stdout so that the
-/// tool will work in a pipeline.
-fn handle_query(input: &str, api_url: &str, token: &str, verbose: bool) {
-    let result = api::dispatch_command(input, api_url, token, verbose);
-    match result {
-        Ok(response_text) => {
-            // Parse and pretty print JSON response
-            match serde_json::from_str::<api::QueryResponse>(&response_text) {
-                Ok(data) => {
-                    if verbose {
-                        eprintln!("{}", "Command:".cyan());
-                        eprintln!("{}", data.command);
-                        eprintln!();
-                        eprintln!("{}", "Trace ID:".cyan());
-                        eprintln!("{}", data.trace_id);
-                        eprintln!();
-                    }
-                    eprintln!("{}", "Search ID:".cyan());
-                    eprintln!("{}", data.search_id);
-                    eprintln!();
-                    eprintln!("{}", "Results:");
-                    match serde_json::to_string_pretty(&data.results) {
-                        Ok(pretty_json) => println!("{}", pretty_json),
-                        Err(_) => println!("{}", response_text), // Fallback to raw text
-                    }
-                }
-                Err(e) => {
-                    if verbose {
-                        eprintln!("❌ Failed to parse response as JSON: {}", e);
-                    }
-                    eprintln!("{}", response_text); // Output raw response if not valid JSON
+/// A script with a single statement is run just like [`process_command`].
+/// Otherwise each statement's output is preceded by a header on stderr, and
+/// the script stops at the first statement that fails unless
+/// `--continue-on-error` is given. Exits with a non-zero status if any
+/// statement failed.
+///
+/// Variables are substituted into the whole script first, so nothing is run
+/// if any of them is unbound.
+pub fn process_script(input: &str, connection: &Connection, args: &Args, variables: &Variables) {
+    let input = &substitute(input, variables);
+    let pieces: Vec<_> = script::split(input)
+        .into_iter()
+        .filter(|piece| piece.statement.is_some())
+        .collect();
+    if pieces.len() <= 1 {
+        let statement = pieces
+            .first()
+            .map_or(input.trim(), |piece| piece.text(input));
+        return process_command(statement, connection, args);
+    }
+
+    let total = pieces.len();
+    let mut failed = 0;
+    for (i, piece) in pieces.iter().enumerate() {
+        let text = piece.text(input);
+        let line = piece.statement.map_or(1, |span| span.line);
+        eprintln!(
+            "{}",
+            format!(
+                "── Statement {} of {} (line {}): {}",
+                i + 1,
+                total,
+                line,
+                text.lines().next().unwrap_or_default()
+            )
+            .cyan()
+        );
+        if !run_command(text, connection, args) {
+            failed += 1;
+            if !args.continue_on_error {
+                let skipped = total - i - 1;
+                if skipped > 0 {
+                    eprintln!(
+                        "{}",
+                        format!(
+                            "❌ Stopped after statement {} failed; {} not run (use --continue-on-error to run the rest)",
+                            i + 1,
+                            skipped
+                        )
+                        .red()
+                    );
                 }
+                std::process::exit(1);
             }
         }
-        Err(e) => {
-            eprintln!("❌ Error dispatching command: {e}");
-            std::process::exit(1);
-        }
+        eprintln!();
     }
-}
 
-pub fn process_command(input: &str, api_url: &str, token: &str, verbose: bool) {
-    if input.is_empty() {
-        eprintln!("(╯°□°)╯︵ ┻━┻ Invalid Command");
+    if failed > 0 {
+        eprintln!(
+            "{}",
+            format!("❌ {} of {} statements failed", failed, total).red()
+        );
         std::process::exit(1);
     }
-    let lower_input = input.to_lowercase();
+    eprintln!("✅ All {} statements succeeded", total);
+}
 
-    if lower_input.starts_with("explain connectors") {
-        handle_explain_connectors(input, api_url, token, verbose);
-    } else if lower_input.starts_with("explain schema ") {
-        handle_explain_schema(input, api_url, token, verbose);
-    } else if lower_input.starts_with("explain graphql ") {
-        handle_explain_graphql(input, api_url, token, verbose);
-    } else if lower_input.starts_with("explain version") {
-        handle_explain_version(input, api_url, token, verbose);
-    } else if lower_input.starts_with("explain attributes ") {
-        handle_explain_attributes(input, api_url, token, verbose);
-    } else if lower_input.starts_with("explain ") {
-        handle_explain(input, api_url, token, verbose);
-    } else if lower_input.starts_with("summarize ") {
-        handle_summarize(input, api_url, token, verbose);
-    } else if lower_input.starts_with("validate ") {
-        handle_validate(input, api_url, token, verbose);
-    } else if lower_input.starts_with("query ") {
-        handle_query(input, api_url, token, verbose);
-    } else {
-        eprintln!("(╯°□°)╯︵ ┻━┻ Invalid Command");
+/// Substitute variables into the input, exiting the process if any is unbound
+fn substitute(input: &str, variables: &Variables) -> String {
+    variables.substitute(input).unwrap_or_else(|e| {
+        eprintln!("{}", format!("❌ {}", e).red());
         std::process::exit(1);
-    }
+    })
 }
 
-/// Handle reading an FSQL query piped in on stdin
-pub fn handle_stdin(args: Args, token: &str) {
-    let api_url = format!("https://{}/{}", args.host, args.path);
-
-    // Read all of stdin
+/// Read all of stdin, exiting the process if it can't be read
+pub fn read_stdin() -> String {
     let mut buffer = String::new();
-    match io::stdin().read_to_string(&mut buffer) {
-        Ok(_) => {}
-        Err(e) => {
-            eprintln!("Error reading from stdin: {}", e);
-            std::process::exit(1);
-        }
+    if let Err(e) = io::stdin().read_to_string(&mut buffer) {
+        eprintln!("Error reading from stdin: {}", e);
+        std::process::exit(1);
     }
-
-    let input = buffer.trim();
-    process_command(input, &api_url, token, args.verbose);
+    buffer
 }
 
-/// Handle loading an FSQL query from a file.
-pub fn handle_file(args: Args, token: &str, file_path: &str) {
-    let api_url = format!("https://{}/{}", args.host, args.path);
-
-    // Read all from file
-    let buffer = match fs::read_to_string(file_path) {
+/// Read a whole file, exiting the process if it can't be read
+pub fn read_file(file_path: &str) -> String {
+    match fs::read_to_string(file_path) {
         Ok(content) => content,
         Err(e) => {
             eprintln!("Error reading from file '{}': {}", file_path, e);
             std::process::exit(1);
         }
+    }
+}
+
+/// Check the syntax of each statement locally, without contacting the API
+///
+/// Exits the process with a non-zero status if any statement doesn't parse.
+pub fn validate_offline(input: &str, variables: &Variables) {
+    let input = &substitute(input, variables);
+    let (statements, errors) = check_syntax(input);
+
+    for e in &errors {
+        eprintln!("{}", e.render(input).red());
+    }
+    if !errors.is_empty() {
+        std::process::exit(1);
+    }
+    if statements > 1 {
+        eprintln!("✅ All {} statements are valid", statements);
+    } else {
+        eprintln!("✅ Query is valid");
+    }
+}
+
+/// Parse each statement in the input, returning how many there are and the
+/// errors found
+fn check_syntax(input: &str) -> (usize, Vec<fsql::ParseError>) {
+    let pieces: Vec<_> = script::split(input)
+        .into_iter()
+        .filter(|piece| piece.statement.is_some())
+        .collect();
+    let errors = if pieces.is_empty() {
+        fsql::parse(input).err().into_iter().collect()
+    } else {
+        pieces
+            .iter()
+            .filter_map(|piece| piece.parse(input).err())
+            .collect()
     };
+    (pieces.len(), errors)
+}
 
-    let input = buffer.trim();
-    process_command(input, &api_url, token, args.verbose);
+/// Handle reading an FSQL query piped in on stdin
+pub fn handle_stdin(args: Args, connection: &Connection, variables: &Variables) {
+    let buffer = read_stdin();
+    process_script(&buffer, connection, &args, variables);
+}
+
+/// Handle loading an FSQL query from a file.
+pub fn handle_file(args: Args, connection: &Connection, variables: &Variables, file_path: &str) {
+    let buffer = read_file(file_path);
+    process_script(&buffer, connection, &args, variables);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_syntax() {
+        // Unquoted dotted values are accepted by the API, so they must be here
+        let (statements, errors) = check_syntax(
+            "QUERY a.* WITH a.ip = 10.0.0.1 AND a.file = cmd.exe;\nQUERY b.* AFTER 2025-01-01T10:00:00.5Z;",
+        );
+        assert_eq!(statements, 2);
+        assert!(errors.is_empty(), "{:?}", errors);
+
+        let (_, errors) = check_syntax("QUERY a.*;\nQUERY b.* WITH;");
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].span.line, 2);
+    }
 }
@@ -0,0 +1,302 @@
+//! Variables substituted into queries before they're sent
+//!
+//! A query can hold `${name}` or `:name` placeholders, filled in from
+//! `--var name=value`, a `--vars-file` TOML file, or `\set` in the REPL. The
+//! values are inserted as FSQL literals: numbers and relative times as they
+//! are, anything else as a quoted string with `'` and `\` escaped, so an IOC
+//! can never change the shape of the query. Placeholders inside string
+//! literals and comments are left alone.
+
+use crate::Args;
+use crate::fsql::lexer::{self, TokenKind};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+/// Values to substitute for placeholders, keyed by name
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Variables {
+    /// The FSQL literal each name is replaced with
+    values: BTreeMap<String, String>,
+}
+
+impl Variables {
+    /// Build the variables from `--vars-file` and `--var`
+    ///
+    /// Values given with `--var` take precedence over those in the file.
+    pub fn from_args(args: &Args) -> Result<Self, String> {
+        let mut variables = match &args.vars_file {
+            Some(path) => Self::from_file(path)?,
+            None => Self::default(),
+        };
+        for var in &args.var {
+            let (name, value) = var
+                .split_once('=')
+                .ok_or_else(|| format!("Expected NAME=VALUE for --var, got '{}'", var))?;
+            variables.set(name.trim(), value)?;
+        }
+        Ok(variables)
+    }
+
+    /// Read variables from a TOML file of `name = value` pairs
+    pub fn from_file(path: &Path) -> Result<Self, String> {
+        let contents = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        Self::from_toml(&contents).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    /// Parse variables from TOML
+    fn from_toml(contents: &str) -> Result<Self, String> {
+        Self::from_table(contents.parse().map_err(|e| format!("{}", e))?)
+    }
+
+    /// Build variables from a TOML table
+    ///
+    /// Strings are treated like values given on the command line; numbers
+    /// and booleans are inserted as they are.
+    pub fn from_table(table: toml::Table) -> Result<Self, String> {
+        let mut variables = Self::default();
+        for (name, value) in table {
+            check_name(&name)?;
+            let literal = match value {
+                toml::Value::String(text) => literal(&text),
+                toml::Value::Integer(n) => n.to_string(),
+                toml::Value::Float(n) => n.to_string(),
+                toml::Value::Boolean(b) => b.to_string(),
+                _ => {
+                    return Err(format!("'{}' must be a string, number or boolean", name));
+                }
+            };
+            variables.values.insert(name, literal);
+        }
+        Ok(variables)
+    }
+
+    /// Set a variable from text given by the user
+    pub fn set(&mut self, name: &str, value: &str) -> Result<(), String> {
+        check_name(name)?;
+        self.values.insert(name.to_string(), literal(value));
+        Ok(())
+    }
+
+    /// Add the other variables, replacing any with the same name
+    pub fn extend(&mut self, other: Variables) {
+        self.values.extend(other.values);
+    }
+
+    /// Remove a variable, returning whether it was set
+    pub fn unset(&mut self, name: &str) -> bool {
+        self.values.remove(name).is_some()
+    }
+
+    /// The literal a variable is replaced with
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.values.get(name).map(String::as_str)
+    }
+
+    /// Each variable and the literal it's replaced with, in name order
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.values
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Replace every placeholder in the source with its value
+    ///
+    /// Fails, naming every unbound variable, if any placeholder has no value.
+    pub fn substitute(&self, source: &str) -> Result<String, String> {
+        // Placeholders in strings and comments aren't placeholders at all
+        let literals: Vec<_> = lexer::tokenize(source)
+            .into_iter()
+            .filter(|token| {
+                matches!(
+                    token.kind,
+                    TokenKind::String | TokenKind::Unterminated | TokenKind::Comment
+                )
+            })
+            .map(|token| token.span)
+            .collect();
+
+        let mut text = String::with_capacity(source.len());
+        let mut unbound: Vec<&str> = Vec::new();
+        let mut offset = 0;
+        while let Some(c) = source[offset..].chars().next() {
+            if let Some(span) = literals
+                .iter()
+                .find(|span| span.start <= offset && offset < span.end)
+            {
+                text.push_str(&source[offset..span.end]);
+                offset = span.end;
+                continue;
+            }
+            let previous = source[..offset].chars().next_back();
+            match placeholder(&source[offset..], previous) {
+                Some((name, len)) => {
+                    match self.get(name) {
+                        Some(value) => text.push_str(value),
+                        None => {
+                            if !unbound.contains(&name) {
+                                unbound.push(name);
+                            }
+                        }
+                    }
+                    offset += len;
+                }
+                None => {
+                    text.push(c);
+                    offset += c.len_utf8();
+                }
+            }
+        }
+
+        match unbound.as_slice() {
+            [] => Ok(text),
+            [name] => Err(format!("Unbound variable '{}'", name)),
+            names => Err(format!("Unbound variables: {}", names.join(", "))),
+        }
+    }
+}
+
+/// Check that a name can be used in a placeholder
+fn check_name(name: &str) -> Result<(), String> {
+    if is_name(name) {
+        Ok(())
+    } else {
+        Err(format!(
+            "Invalid variable name '{}': use letters, digits and '_', not starting with a digit",
+            name
+        ))
+    }
+}
+
+fn is_name(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// The length of the name at the start of the text
+fn name_length(text: &str) -> usize {
+    if !text.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') {
+        return 0;
+    }
+    text.find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+        .unwrap_or(text.len())
+}
+
+/// If the text starts with a placeholder, return its name and length
+///
+/// `:name` only counts when it doesn't follow part of a word, so that times
+/// such as `10:30` and paths are left alone.
+fn placeholder(text: &str, previous: Option<char>) -> Option<(&str, usize)> {
+    if let Some(rest) = text.strip_prefix("${") {
+        let len = name_length(rest);
+        return (len > 0 && rest[len..].starts_with('}')).then(|| (&rest[..len], len + 3));
+    }
+    let rest = text.strip_prefix(':')?;
+    let follows_word = previous
+        .is_some_and(|c| c.is_alphanumeric() || matches!(c, '_' | '.' | '*' | '-' | ':' | '$'));
+    let len = name_length(rest);
+    (len > 0 && !follows_word).then(|| (&rest[..len], len + 1))
+}
+
+/// The FSQL literal for a value given as text
+///
+/// Numbers and relative times are left as they are; anything else is quoted.
+fn literal(value: &str) -> String {
+    match lexer::tokenize(value).as_slice() {
+        [token]
+            if matches!(token.kind, TokenKind::Number | TokenKind::Duration)
+                && token.text(value) == value =>
+        {
+            value.to_string()
+        }
+        _ => quote(value),
+    }
+}
+
+/// Quote a value as an FSQL string literal
+fn quote(value: &str) -> String {
+    format!("'{}'", value.replace('\\', "\\\\").replace('\'', "\\'"))
+}
+
+/// Remove the quotes and escapes from a value written as a string literal
+///
+/// Anything else is returned as it is.
+pub fn unquote(value: &str) -> String {
+    match lexer::tokenize(value).as_slice() {
+        [token] if token.kind == TokenKind::String && token.text(value) == value => {
+            let mut text = String::new();
+            let mut chars = value[1..value.len() - 1].chars();
+            while let Some(c) = chars.next() {
+                if c == '\\' {
+                    text.extend(chars.next());
+                } else {
+                    text.push(c);
+                }
+            }
+            text
+        }
+        _ => value.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_substitute() {
+        let mut variables = Variables::default();
+        variables.set("file", "it's C:\\evil.exe").unwrap();
+        variables.set("port", "443").unwrap();
+        variables.set("window", "24h").unwrap();
+
+        assert_eq!(
+            variables
+                .substitute("QUERY a.* WITH a.name = ${file} AND a.port = :port AFTER :window")
+                .unwrap(),
+            "QUERY a.* WITH a.name = 'it\\'s C:\\\\evil.exe' AND a.port = 443 AFTER 24h"
+        );
+        // Strings, comments, times and paths are left alone
+        let source = "QUERY a.* WITH a.b = ':file ${file}' AFTER '10:30' -- :port\n";
+        assert_eq!(variables.substitute(source).unwrap(), source);
+        assert_eq!(
+            variables.substitute("a.b::c x:port").unwrap(),
+            "a.b::c x:port"
+        );
+
+        assert_eq!(
+            variables.substitute("a = :ioc OR b = ${ioc} OR c = :other"),
+            Err("Unbound variables: ioc, other".to_string())
+        );
+        assert_eq!(
+            variables.substitute("a = :ioc"),
+            Err("Unbound variable 'ioc'".to_string())
+        );
+    }
+
+    #[test]
+    fn test_variables_file() {
+        let variables =
+            Variables::from_toml("domain = \"evil.com\"\nport = 443\nwindow = \"7d\"\nflag = true")
+                .unwrap();
+        assert_eq!(variables.get("domain"), Some("'evil.com'"));
+        assert_eq!(variables.get("port"), Some("443"));
+        assert_eq!(variables.get("window"), Some("7d"));
+        assert_eq!(variables.get("flag"), Some("true"));
+
+        assert!(Variables::from_toml("list = [1, 2]").is_err());
+        assert!(Variables::from_toml("\"bad name\" = 1").is_err());
+    }
+
+    #[test]
+    fn test_unquote() {
+        assert_eq!(unquote("'a -- b'"), "a -- b");
+        assert_eq!(unquote("\"it\\'s\""), "it's");
+        assert_eq!(unquote("evil.com"), "evil.com");
+    }
+}
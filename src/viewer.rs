@@ -0,0 +1,878 @@
+//! A full-screen viewer for exploring QUERY results
+//!
+//! Opened with `\view` in the REPL, or in place of printed results with
+//! `--tui`. The top of the screen is a scrollable table of the flattened
+//! results and the bottom shows the full JSON of the selected event. Columns
+//! can be hidden and reordered, rows searched incrementally, a value copied
+//! to the clipboard and the current view exported to a file.
+//!
+//! The viewer is drawn with ANSI escape sequences on the controlling
+//! terminal (`/dev/tty`), so it works even when stdin is a pipe. Copying uses
+//! the OSC 52 escape sequence, which most terminal emulators support.
+
+use crate::api::QueryResponse;
+use crate::output::{self, OutputFormat, OutputOptions};
+use clap::ValueEnum;
+use std::path::Path;
+
+/// Widest a column is drawn, in characters
+const MAX_COLUMN_WIDTH: usize = 40;
+
+/// Separator placed between table columns
+const COLUMN_SEPARATOR: &str = " │ ";
+
+/// The keys, shown at the bottom of the screen
+const HELP: &str = "q quit  ↑↓ row  ←→ column  x hide  u unhide  < > move  / search  n/N next  y copy  Y copy event  e export  J/K scroll event";
+
+const BOLD: &str = "\x1b[1m";
+const DIM: &str = "\x1b[2m";
+const UNDERLINE: &str = "\x1b[4m";
+const REVERSE: &str = "\x1b[7m";
+const NORMAL: &str = "\x1b[22m";
+const RESET: &str = "\x1b[0m";
+
+/// A key press
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Key {
+    Char(char),
+    Up,
+    Down,
+    Left,
+    Right,
+    PageUp,
+    PageDown,
+    Home,
+    End,
+    Enter,
+    Backspace,
+    Esc,
+    /// Ctrl+C
+    Interrupt,
+}
+
+/// Decode the bytes read from the terminal into keys
+///
+/// Escape sequences that aren't understood are dropped.
+fn parse_keys(bytes: &[u8]) -> Vec<Key> {
+    let text = String::from_utf8_lossy(bytes);
+    let mut chars = text.chars().peekable();
+    let mut keys = Vec::new();
+    while let Some(c) = chars.next() {
+        let key = match c {
+            '\x1b' if matches!(chars.peek(), Some('[' | 'O')) => {
+                chars.next();
+                let mut sequence = String::new();
+                for next in chars.by_ref() {
+                    sequence.push(next);
+                    if next.is_ascii_alphabetic() || next == '~' {
+                        break;
+                    }
+                }
+                match sequence.as_str() {
+                    "A" => Key::Up,
+                    "B" => Key::Down,
+                    "C" => Key::Right,
+                    "D" => Key::Left,
+                    "H" | "1~" | "7~" => Key::Home,
+                    "F" | "4~" | "8~" => Key::End,
+                    "5~" => Key::PageUp,
+                    "6~" => Key::PageDown,
+                    _ => continue,
+                }
+            }
+            '\x1b' => Key::Esc,
+            '\r' | '\n' => Key::Enter,
+            '\x7f' | '\x08' => Key::Backspace,
+            '\x03' => Key::Interrupt,
+            c if c.is_control() => continue,
+            c => Key::Char(c),
+        };
+        keys.push(key);
+    }
+    keys
+}
+
+/// What's being typed at the bottom of the screen
+enum Mode {
+    Normal,
+    /// Searching as the query is typed, from the row selected at the start
+    Search {
+        query: String,
+        origin: usize,
+    },
+    /// Typing the path to export to
+    Export {
+        path: String,
+    },
+}
+
+/// What the viewer needs done after a key
+#[derive(Debug, PartialEq)]
+enum Action {
+    None,
+    Quit,
+    Copy(String),
+    Export(String),
+}
+
+/// The state of the viewer
+struct Viewer<'a> {
+    data: &'a QueryResponse,
+    /// Flattened column paths
+    columns: Vec<String>,
+    /// The text of every cell, by row then column
+    cells: Vec<Vec<String>>,
+    /// Width each column is drawn at
+    widths: Vec<usize>,
+    /// Columns in the order they're shown
+    order: Vec<usize>,
+    hidden: Vec<bool>,
+    /// The selected row
+    row: usize,
+    /// The first row on screen
+    top: usize,
+    /// The selected column, as a position among the visible columns
+    column: usize,
+    /// The first visible column on screen
+    left: usize,
+    /// Rows of the table on screen, for paging
+    page: usize,
+    /// Lines of the event's JSON scrolled past
+    detail_scroll: usize,
+    /// The last search, for `n` and `N`
+    search: String,
+    mode: Mode,
+    /// Shown in place of the help until the next key
+    message: Option<String>,
+}
+
+impl<'a> Viewer<'a> {
+    fn new(data: &'a QueryResponse) -> Self {
+        let rows: Vec<_> = data.results.iter().map(output::flatten).collect();
+        let columns = output::columns(&rows);
+        let cells: Vec<Vec<String>> = rows
+            .iter()
+            .map(|row| {
+                output::row_values(row, &columns)
+                    .into_iter()
+                    .map(|value| {
+                        value
+                            .map(|value| output::single_line(&output::cell_text(value)))
+                            .unwrap_or_default()
+                    })
+                    .collect()
+            })
+            .collect();
+        let widths = columns
+            .iter()
+            .enumerate()
+            .map(|(i, column)| {
+                cells
+                    .iter()
+                    .map(|row| row[i].chars().count())
+                    .chain([column.chars().count()])
+                    .max()
+                    .unwrap_or_default()
+                    .clamp(1, MAX_COLUMN_WIDTH)
+            })
+            .collect();
+        Self {
+            data,
+            order: (0..columns.len()).collect(),
+            hidden: vec![false; columns.len()],
+            columns,
+            cells,
+            widths,
+            row: 0,
+            top: 0,
+            column: 0,
+            left: 0,
+            page: 1,
+            detail_scroll: 0,
+            search: String::new(),
+            mode: Mode::Normal,
+            message: None,
+        }
+    }
+
+    /// The columns that aren't hidden, in the order they're shown
+    fn visible(&self) -> Vec<usize> {
+        self.order
+            .iter()
+            .copied()
+            .filter(|&column| !self.hidden[column])
+            .collect()
+    }
+
+    /// The selected column, as an index into `columns`
+    fn selected_column(&self) -> Option<usize> {
+        self.visible().get(self.column).copied()
+    }
+
+    fn select_row(&mut self, row: usize) {
+        let row = row.min(self.cells.len().saturating_sub(1));
+        if row != self.row {
+            self.row = row;
+            self.detail_scroll = 0;
+        }
+    }
+
+    /// Find the next row, starting at `start`, with a visible cell containing
+    /// the query, ignoring case
+    fn find(&self, query: &str, start: usize, forward: bool) -> Option<usize> {
+        let query = query.to_lowercase();
+        let visible = self.visible();
+        let count = self.cells.len();
+        (0..count)
+            .map(|step| {
+                if forward {
+                    (start + step) % count
+                } else {
+                    (start + count - step % count) % count
+                }
+            })
+            .find(|&row| {
+                visible
+                    .iter()
+                    .any(|&column| self.cells[row][column].to_lowercase().contains(&query))
+            })
+    }
+
+    /// Jump to the next match of the last search
+    fn find_next(&mut self, forward: bool) {
+        if self.search.is_empty() {
+            self.message = Some("Nothing to find yet - press / to search".to_string());
+            return;
+        }
+        let count = self.cells.len();
+        let start = if forward {
+            self.row + 1
+        } else {
+            self.row + count - 1
+        };
+        match self.find(&self.search, start % count, forward) {
+            Some(row) => self.select_row(row),
+            None => self.message = Some(format!("No match for '{}'", self.search)),
+        }
+    }
+
+    /// Move the selected column one place left or right among the visible
+    /// columns
+    fn move_column(&mut self, right: bool) {
+        let visible = self.visible();
+        let other = if right {
+            self.column + 1
+        } else {
+            match self.column.checked_sub(1) {
+                Some(other) => other,
+                None => return,
+            }
+        };
+        if other >= visible.len() {
+            return;
+        }
+        let position = |column| self.order.iter().position(|&c| c == column);
+        if let (Some(a), Some(b)) = (position(visible[self.column]), position(visible[other])) {
+            self.order.swap(a, b);
+            self.column = other;
+        }
+    }
+
+    /// Update the viewer for a key
+    fn handle(&mut self, key: Key) -> Action {
+        self.message = None;
+        match &mut self.mode {
+            Mode::Search { query, origin } => {
+                let origin = *origin;
+                match key {
+                    Key::Char(c) => query.push(c),
+                    Key::Backspace => {
+                        query.pop();
+                    }
+                    Key::Enter => {
+                        self.search = std::mem::take(query);
+                        self.mode = Mode::Normal;
+                        return Action::None;
+                    }
+                    Key::Esc | Key::Interrupt => {
+                        self.mode = Mode::Normal;
+                        self.select_row(origin);
+                        return Action::None;
+                    }
+                    _ => return Action::None,
+                }
+                let query = query.clone();
+                match self.find(&query, origin, true) {
+                    Some(row) => self.select_row(row),
+                    None if query.is_empty() => self.select_row(origin),
+                    None => self.message = Some(format!("No match for '{}'", query)),
+                }
+                return Action::None;
+            }
+            Mode::Export { path } => {
+                match key {
+                    Key::Char(c) => path.push(c),
+                    Key::Backspace => {
+                        path.pop();
+                    }
+                    Key::Enter => {
+                        let path = std::mem::take(path);
+                        self.mode = Mode::Normal;
+                        if !path.trim().is_empty() {
+                            return Action::Export(path.trim().to_string());
+                        }
+                    }
+                    Key::Esc | Key::Interrupt => self.mode = Mode::Normal,
+                    _ => {}
+                }
+                return Action::None;
+            }
+            Mode::Normal => {}
+        }
+
+        let last_row = self.cells.len().saturating_sub(1);
+        let visible = self.visible();
+        match key {
+            Key::Char('q') | Key::Esc | Key::Interrupt => return Action::Quit,
+            Key::Up | Key::Char('k') => self.select_row(self.row.saturating_sub(1)),
+            Key::Down | Key::Char('j') => self.select_row(self.row + 1),
+            Key::PageUp => self.select_row(self.row.saturating_sub(self.page)),
+            Key::PageDown => self.select_row(self.row + self.page),
+            Key::Home | Key::Char('g') => self.select_row(0),
+            Key::End | Key::Char('G') => self.select_row(last_row),
+            Key::Left | Key::Char('h') => self.column = self.column.saturating_sub(1),
+            Key::Right | Key::Char('l') => {
+                self.column = (self.column + 1).min(visible.len().saturating_sub(1))
+            }
+            Key::Char('x') => match self.selected_column() {
+                Some(column) if visible.len() > 1 => {
+                    self.hidden[column] = true;
+                    self.column = self.column.min(visible.len() - 2);
+                    self.message = Some(format!(
+                        "Hid {} - press u to show it again",
+                        self.columns[column]
+                    ));
+                }
+                _ => self.message = Some("The last column can't be hidden".to_string()),
+            },
+            Key::Char('u') => self.hidden.iter_mut().for_each(|hidden| *hidden = false),
+            Key::Char('<') => self.move_column(false),
+            Key::Char('>') => self.move_column(true),
+            Key::Char('/') => {
+                self.mode = Mode::Search {
+                    query: String::new(),
+                    origin: self.row,
+                }
+            }
+            Key::Char('n') => self.find_next(true),
+            Key::Char('N') => self.find_next(false),
+            Key::Char('y') => {
+                if let Some(column) = self.selected_column() {
+                    self.message = Some(format!("Copied {}", self.columns[column]));
+                    return Action::Copy(self.cells[self.row][column].clone());
+                }
+            }
+            Key::Char('Y') => {
+                if let Ok(json) = serde_json::to_string_pretty(&self.data.results[self.row]) {
+                    self.message = Some(format!("Copied event {}", self.row + 1));
+                    return Action::Copy(json);
+                }
+            }
+            Key::Char('e') => {
+                self.mode = Mode::Export {
+                    path: String::new(),
+                }
+            }
+            Key::Char('J') => self.detail_scroll += 1,
+            Key::Char('K') => self.detail_scroll = self.detail_scroll.saturating_sub(1),
+            _ => {}
+        }
+        Action::None
+    }
+
+    /// How many of the columns, from the first, fit across the screen
+    ///
+    /// At least one column is always shown, truncated if need be.
+    fn fitting(&self, columns: &[usize], width: usize) -> usize {
+        let mut used = 0;
+        let mut count = 0;
+        for &column in columns {
+            if count > 0 {
+                used += COLUMN_SEPARATOR.chars().count();
+            }
+            used += self.widths[column];
+            if used > width && count > 0 {
+                break;
+            }
+            count += 1;
+        }
+        count
+    }
+
+    /// Draw the header (with no row) or a row of the table
+    fn table_line(&self, shown: &[usize], width: usize, row: Option<usize>) -> String {
+        let selected = self.selected_column();
+        let is_selected_row = row == Some(self.row);
+        let mut line = String::new();
+        let mut used = 0;
+        for (n, &column) in shown.iter().enumerate() {
+            if n > 0 {
+                line.push_str(COLUMN_SEPARATOR);
+                used += COLUMN_SEPARATOR.chars().count();
+            }
+            let cell_width = self.widths[column].min(width.saturating_sub(used));
+            let text = match row {
+                None => output::truncate_start(&self.columns[column], cell_width),
+                Some(row) => output::truncate(&self.cells[row][column], cell_width),
+            };
+            let text = output::pad(&text, cell_width);
+            used += cell_width;
+            match (row, Some(column) == selected) {
+                (None, true) => line.push_str(&format!("{}{}{}{}", BOLD, UNDERLINE, text, RESET)),
+                (None, false) => line.push_str(&format!("{}{}{}", BOLD, text, RESET)),
+                (Some(_), true) if is_selected_row => {
+                    line.push_str(&format!("{}{}{}", BOLD, text, NORMAL))
+                }
+                (Some(_), _) => line.push_str(&text),
+            }
+        }
+        if is_selected_row {
+            format!(
+                "{}{}{}{}",
+                REVERSE,
+                line,
+                " ".repeat(width.saturating_sub(used)),
+                RESET
+            )
+        } else {
+            line
+        }
+    }
+
+    /// Draw the whole screen, one string per line
+    fn render(&mut self, width: usize, height: usize) -> Vec<String> {
+        let width = width.max(20);
+        let height = height.max(4);
+        let count = self.cells.len();
+
+        // The table takes the top of the screen and the event the rest,
+        // unless there isn't room for both
+        let body = height - 2;
+        let (table_height, detail_height) = if body >= 8 {
+            let table = body * 3 / 5;
+            (table, body - table - 1)
+        } else {
+            (body, 0)
+        };
+        self.page = table_height.saturating_sub(1).max(1);
+        if self.row < self.top {
+            self.top = self.row;
+        } else if self.row >= self.top + self.page {
+            self.top = self.row + 1 - self.page;
+        }
+
+        // Scroll sideways to keep the selected column on screen
+        let visible = self.visible();
+        self.column = self.column.min(visible.len().saturating_sub(1));
+        self.left = self.left.min(self.column);
+        while self.column >= self.left + self.fitting(&visible[self.left..], width) {
+            self.left += 1;
+        }
+        let shown = &visible[self.left..self.left + self.fitting(&visible[self.left..], width)];
+
+        let mut lines = Vec::with_capacity(height);
+        let title = format!(
+            " Results · row {} of {} · {} of {} columns",
+            self.row + 1,
+            count,
+            visible.len(),
+            self.columns.len()
+        );
+        lines.push(format!(
+            "{}{}{}",
+            REVERSE,
+            output::pad(&output::truncate(&title, width), width),
+            RESET
+        ));
+        lines.push(self.table_line(shown, width, None));
+        for row in self.top..(self.top + self.page).min(count) {
+            lines.push(self.table_line(shown, width, Some(row)));
+        }
+        lines.resize(1 + table_height, String::new());
+
+        if detail_height > 0 {
+            let heading = format!("── Event {} of {} ", self.row + 1, count);
+            let fill = width.saturating_sub(heading.chars().count());
+            lines.push(format!("{}{}{}{}", DIM, heading, "─".repeat(fill), RESET));
+            let json =
+                serde_json::to_string_pretty(&self.data.results[self.row]).unwrap_or_default();
+            let json: Vec<&str> = json.lines().collect();
+            self.detail_scroll = self
+                .detail_scroll
+                .min(json.len().saturating_sub(detail_height));
+            for line in json.iter().skip(self.detail_scroll).take(detail_height) {
+                lines.push(output::truncate(line, width));
+            }
+            lines.resize(height - 1, String::new());
+        }
+
+        let status = match &self.mode {
+            Mode::Search { query, .. } => format!("/{}", query),
+            Mode::Export { path } => format!(
+                "Export to (.csv, .tsv, .json, .ndjson, .parquet or .arrow): {}",
+                path
+            ),
+            Mode::Normal => match &self.message {
+                Some(message) => output::truncate(message, width),
+                None => format!("{}{}", DIM, output::truncate(HELP, width)),
+            },
+        };
+        lines.push(status);
+        lines
+    }
+
+    /// Write the rows with the visible columns, in order, to a file
+    ///
+    /// The format comes from the file's extension, falling back to CSV.
+    fn export(&self, path: &Path, options: &OutputOptions) -> Result<String, String> {
+        let format = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .and_then(|extension| OutputFormat::from_str(extension, true).ok())
+            .unwrap_or(OutputFormat::Csv);
+        let options = OutputOptions {
+            format,
+            columns: Some(
+                self.visible()
+                    .into_iter()
+                    .map(|column| self.columns[column].clone())
+                    .collect(),
+            ),
+            out_file: None,
+            ..options.clone()
+        };
+        output::write_results_to_file(path, self.data, &options)
+            .map(|summary| summary.to_string())
+            .map_err(|e| format!("Failed to export: {}", e))
+    }
+}
+
+/// Open the viewer on the results, returning when the user quits
+///
+/// Exports use `options` for everything but the format and columns.
+#[cfg(unix)]
+pub fn show(data: &QueryResponse, options: &OutputOptions) -> Result<(), String> {
+    if data.results.is_empty() {
+        return Err("There are no results to view".to_string());
+    }
+    let mut terminal =
+        terminal::Terminal::open().map_err(|e| format!("Failed to open the terminal: {}", e))?;
+    let mut viewer = Viewer::new(data);
+    let failed = |e: std::io::Error| format!("Failed to draw the viewer: {}", e);
+    loop {
+        let (width, height) = terminal.size();
+        terminal
+            .draw(&viewer.render(width, height))
+            .map_err(failed)?;
+        let keys = match terminal.read_keys() {
+            Ok(keys) => keys,
+            // Interrupted by a signal such as a resize: just draw again
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(failed(e)),
+        };
+        for key in keys {
+            match viewer.handle(key) {
+                Action::None => {}
+                Action::Quit => return Ok(()),
+                Action::Copy(text) => terminal.copy(&text).map_err(failed)?,
+                Action::Export(path) => {
+                    viewer.message = Some(match viewer.export(Path::new(&path), options) {
+                        Ok(summary) => summary,
+                        Err(e) => e,
+                    })
+                }
+            }
+        }
+    }
+}
+
+/// The viewer drives the terminal through termios, so it's only available
+/// on Unix; elsewhere `\\view` and `--tui` report this error instead
+#[cfg(not(unix))]
+pub fn show(_data: &QueryResponse, _options: &OutputOptions) -> Result<(), String> {
+    Err("The result viewer needs a Unix terminal".to_string())
+}
+
+/// The controlling terminal, in raw mode on the alternate screen
+#[cfg(unix)]
+mod terminal {
+    use super::{Key, parse_keys};
+    use base64::Engine;
+    use base64::engine::general_purpose::STANDARD as BASE64;
+    use std::fs::{File, OpenOptions};
+    use std::io::{self, Read, Write};
+    use std::os::fd::AsRawFd;
+
+    /// Restores the terminal as it was when dropped
+    pub struct Terminal {
+        tty: File,
+        original: libc::termios,
+    }
+
+    impl Terminal {
+        pub fn open() -> io::Result<Self> {
+            let tty = OpenOptions::new().read(true).write(true).open("/dev/tty")?;
+            let fd = tty.as_raw_fd();
+            // SAFETY: termios is plain data, and is filled in by tcgetattr
+            // before it's used
+            let mut original: libc::termios = unsafe { std::mem::zeroed() };
+            if unsafe { libc::tcgetattr(fd, &mut original) } != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let mut raw = original;
+            // SAFETY: raw is a valid termios, and fd is open for the lifetime
+            // of the call
+            unsafe { libc::cfmakeraw(&mut raw) };
+            if unsafe { libc::tcsetattr(fd, libc::TCSANOW, &raw) } != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let mut terminal = Self { tty, original };
+            // Switch to the alternate screen and hide the cursor
+            terminal.tty.write_all(b"\x1b[?1049h\x1b[?25l")?;
+            Ok(terminal)
+        }
+
+        /// The width and height of the terminal
+        pub fn size(&self) -> (usize, usize) {
+            terminal_size::terminal_size_of(&self.tty)
+                .map(|(width, height)| (width.0 as usize, height.0 as usize))
+                .unwrap_or((80, 24))
+        }
+
+        /// Replace the screen with the lines
+        pub fn draw(&mut self, lines: &[String]) -> io::Result<()> {
+            let mut frame = String::from("\x1b[H");
+            for (i, line) in lines.iter().enumerate() {
+                if i > 0 {
+                    frame.push_str("\r\n");
+                }
+                frame.push_str("\x1b[2K");
+                frame.push_str(line);
+                frame.push_str(super::RESET);
+            }
+            self.tty.write_all(frame.as_bytes())?;
+            self.tty.flush()
+        }
+
+        /// Wait for input and decode it
+        pub fn read_keys(&mut self) -> io::Result<Vec<Key>> {
+            let mut buffer = [0; 64];
+            let read = self.tty.read(&mut buffer)?;
+            Ok(parse_keys(&buffer[..read]))
+        }
+
+        /// Put text on the clipboard with the OSC 52 escape sequence
+        pub fn copy(&mut self, text: &str) -> io::Result<()> {
+            write!(self.tty, "\x1b]52;c;{}\x07", BASE64.encode(text))?;
+            self.tty.flush()
+        }
+    }
+
+    impl Drop for Terminal {
+        fn drop(&mut self) {
+            // Show the cursor and leave the alternate screen
+            let _ = self.tty.write_all(b"\x1b[?25h\x1b[?1049l");
+            let _ = self.tty.flush();
+            // SAFETY: original came from tcgetattr on the same terminal
+            unsafe { libc::tcsetattr(self.tty.as_raw_fd(), libc::TCSANOW, &self.original) };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn response() -> QueryResponse {
+        QueryResponse {
+            command: "QUERY".to_string(),
+            search_id: "s".to_string(),
+            trace_id: "t".to_string(),
+            results: vec![
+                json!({"time": 1, "actor": {"user": "alice"}, "file": "a.exe"}),
+                json!({"time": 2, "actor": {"user": "bob"}, "file": "regsvr32.exe"}),
+                json!({"time": 3, "actor": {"user": "carol"}, "file": "c.dll"}),
+            ],
+        }
+    }
+
+    #[test]
+    fn test_parse_keys() {
+        assert_eq!(
+            parse_keys(b"j\x1b[A\x1b[6~\x1b\r\x7f\x03\x1b[Z"),
+            vec![
+                Key::Char('j'),
+                Key::Up,
+                Key::PageDown,
+                Key::Esc,
+                Key::Enter,
+                Key::Backspace,
+                Key::Interrupt,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_columns() {
+        let data = response();
+        let mut viewer = Viewer::new(&data);
+        assert_eq!(viewer.columns, vec!["time", "actor.user", "file"]);
+
+        viewer.handle(Key::Right);
+        viewer.handle(Key::Char('>'));
+        assert_eq!(viewer.order, vec![0, 2, 1]);
+        assert_eq!(viewer.selected_column(), Some(1));
+
+        viewer.handle(Key::Char('x'));
+        assert_eq!(viewer.visible(), vec![0, 2]);
+        assert_eq!(viewer.selected_column(), Some(2));
+        viewer.handle(Key::Char('x'));
+        assert_eq!(viewer.handle(Key::Char('x')), Action::None);
+        assert_eq!(viewer.visible(), vec![0]);
+        viewer.handle(Key::Char('u'));
+        assert_eq!(viewer.visible(), vec![0, 2, 1]);
+
+        // Exports keep the order of the visible columns
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("view.csv");
+        let options = OutputOptions {
+            format: OutputFormat::Table,
+            columns: None,
+            with_metadata: false,
+            out_file: None,
+            if_exists: output::ExistingFile::Overwrite,
+        };
+        assert_eq!(
+            viewer.export(&path, &options),
+            Ok(format!("Wrote 3 rows to {}", path.display()))
+        );
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "time,file,actor.user\n1,a.exe,alice\n2,regsvr32.exe,bob\n3,c.dll,carol\n"
+        );
+    }
+
+    #[test]
+    fn test_export_table() {
+        let data = response();
+        let viewer = Viewer::new(&data);
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("view.table");
+        let options = OutputOptions {
+            format: OutputFormat::Json,
+            columns: None,
+            with_metadata: false,
+            out_file: None,
+            if_exists: output::ExistingFile::Overwrite,
+        };
+        viewer.export(&path, &options).unwrap();
+
+        // The viewer runs on a terminal, but the file mustn't be styled
+        let contents = std::fs::read_to_string(&path).unwrap();
+        assert!(!contents.contains('\x1b'), "{:?}", contents);
+        let lines: Vec<&str> = contents.lines().collect();
+        assert_eq!(lines[0].trim_end(), "time │ actor.user │ file");
+        assert_eq!(lines[2], "1    │ alice      │ a.exe");
+    }
+
+    #[test]
+    fn test_export_dotted_keys() {
+        // "a.b" flattens to the same path as a nested "b" under "a", so the
+        // export has to keep it as a key of its own
+        let data = QueryResponse {
+            results: vec![json!({"a": 1, "a.b": 2}), json!({"a.b": 3, "a": 4})],
+            ..response()
+        };
+        let mut viewer = Viewer::new(&data);
+        assert_eq!(viewer.columns, vec!["a", "a.b"]);
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("view.arrow");
+        let options = OutputOptions {
+            format: OutputFormat::Table,
+            columns: None,
+            with_metadata: false,
+            out_file: None,
+            if_exists: output::ExistingFile::Overwrite,
+        };
+        let fields = |path: &Path| -> Vec<String> {
+            let file = std::fs::File::open(path).unwrap();
+            let reader = arrow_ipc::reader::FileReader::try_new(file, None).unwrap();
+            let schema = reader.schema();
+            schema.fields().iter().map(|f| f.name().clone()).collect()
+        };
+        assert_eq!(
+            viewer.export(&path, &options),
+            Ok(format!("Wrote 2 rows to {}", path.display()))
+        );
+        assert_eq!(fields(&path), vec!["a", "a.b"]);
+
+        viewer.handle(Key::Char('>'));
+        viewer.export(&path, &options).unwrap();
+        assert_eq!(fields(&path), vec!["a.b", "a"]);
+    }
+
+    #[test]
+    fn test_search() {
+        let data = response();
+        let mut viewer = Viewer::new(&data);
+        viewer.handle(Key::Char('/'));
+        viewer.handle(Key::Char('R'));
+        assert_eq!(viewer.row, 1);
+        viewer.handle(Key::Char('x'));
+        assert_eq!(viewer.message, Some("No match for 'Rx'".to_string()));
+        viewer.handle(Key::Backspace);
+        viewer.handle(Key::Enter);
+        assert_eq!(viewer.row, 1);
+
+        // 'r' is in carol and regsvr32; n wraps around
+        viewer.handle(Key::Char('n'));
+        assert_eq!(viewer.row, 2);
+        viewer.handle(Key::Char('n'));
+        assert_eq!(viewer.row, 1);
+        viewer.handle(Key::Char('N'));
+        assert_eq!(viewer.row, 2);
+
+        // Escape goes back to where the search started
+        viewer.handle(Key::Char('/'));
+        viewer.handle(Key::Char('a'));
+        assert_eq!(viewer.row, 2);
+        viewer.handle(Key::Char('l'));
+        assert_eq!(viewer.row, 0);
+        viewer.handle(Key::Esc);
+        assert_eq!(viewer.row, 2);
+    }
+
+    #[test]
+    fn test_render() {
+        let data = response();
+        let mut viewer = Viewer::new(&data);
+        viewer.handle(Key::Down);
+        let lines = viewer.render(40, 14);
+        assert_eq!(lines.len(), 14);
+        assert!(lines[0].contains("row 2 of 3"));
+        assert!(lines[3].starts_with(REVERSE));
+        assert!(lines[3].contains("regsvr32.exe"));
+        assert!(lines[8].contains("Event 2 of 3"));
+
+        // Scrolling sideways keeps the selected column on screen
+        viewer.handle(Key::End);
+        let lines = viewer.render(20, 14);
+        assert!(lines[1].contains("time"));
+        viewer.handle(Key::Right);
+        viewer.handle(Key::Right);
+        let lines = viewer.render(20, 14);
+        assert!(lines[1].contains("file"));
+        assert!(!lines[1].contains("time"));
+    }
+}